@@ -12,6 +12,8 @@
 #                 |        \              /                              __/   #
 #                           ¯————————————¯                                     #
 # gen-spirv.sh                                                                 #
+# Regenerates the SPIR-V in src/shaders/res that build.rs uses when the        #
+# `shaderc` feature is off.                                                    #
 
 SPIRV_OPT="spirv-opt --strip-debug --freeze-spec-const --eliminate-dead-const --fold-spec-const-op-composite --unify-const"
 SRC=src/shaders/glsl
@@ -25,33 +27,45 @@ mkdir -p $OUT_OPTIMIZED/
 mkdir -p $OUT_RELEASE/
 
 glslangValidator $SRC/solid-frag.glsl -V -o $OUT_UNOPTIMIZED/solid-frag.spv -S frag
+glslangValidator $SRC/solid-bfrag.glsl -V -o $OUT_UNOPTIMIZED/solid-bfrag.spv -S frag
 glslangValidator $SRC/solid-vert.glsl -V -o $OUT_UNOPTIMIZED/solid-vert.spv -S vert
 $SPIRV_OPT $OUT_UNOPTIMIZED/solid-frag.spv -o $OUT_OPTIMIZED/solid-frag.spv
+$SPIRV_OPT $OUT_UNOPTIMIZED/solid-bfrag.spv -o $OUT_OPTIMIZED/solid-bfrag.spv
 $SPIRV_OPT $OUT_UNOPTIMIZED/solid-vert.spv -o $OUT_OPTIMIZED/solid-vert.spv
 
 glslangValidator $SRC/gradient-frag.glsl -V -o $OUT_UNOPTIMIZED/gradient-frag.spv -S frag
+glslangValidator $SRC/gradient-bfrag.glsl -V -o $OUT_UNOPTIMIZED/gradient-bfrag.spv -S frag
 glslangValidator $SRC/gradient-vert.glsl -V -o $OUT_UNOPTIMIZED/gradient-vert.spv -S vert
 $SPIRV_OPT $OUT_UNOPTIMIZED/gradient-frag.spv -o $OUT_OPTIMIZED/gradient-frag.spv
+$SPIRV_OPT $OUT_UNOPTIMIZED/gradient-bfrag.spv -o $OUT_OPTIMIZED/gradient-bfrag.spv
 $SPIRV_OPT $OUT_UNOPTIMIZED/gradient-vert.spv -o $OUT_OPTIMIZED/gradient-vert.spv
 
 glslangValidator $SRC/texture-frag.glsl -V -o $OUT_UNOPTIMIZED/texture-frag.spv -S frag
+glslangValidator $SRC/texture-bfrag.glsl -V -o $OUT_UNOPTIMIZED/texture-bfrag.spv -S frag
 glslangValidator $SRC/texture-vert.glsl -V -o $OUT_UNOPTIMIZED/texture-vert.spv -S vert
 $SPIRV_OPT $OUT_UNOPTIMIZED/texture-frag.spv -o $OUT_OPTIMIZED/texture-frag.spv
+$SPIRV_OPT $OUT_UNOPTIMIZED/texture-bfrag.spv -o $OUT_OPTIMIZED/texture-bfrag.spv
 $SPIRV_OPT $OUT_UNOPTIMIZED/texture-vert.spv -o $OUT_OPTIMIZED/texture-vert.spv
 
 glslangValidator $SRC/faded-frag.glsl -V -o $OUT_UNOPTIMIZED/faded-frag.spv -S frag
+glslangValidator $SRC/faded-bfrag.glsl -V -o $OUT_UNOPTIMIZED/faded-bfrag.spv -S frag
 glslangValidator $SRC/faded-vert.glsl -V -o $OUT_UNOPTIMIZED/faded-vert.spv -S vert
 $SPIRV_OPT $OUT_UNOPTIMIZED/faded-frag.spv -o $OUT_OPTIMIZED/faded-frag.spv
+$SPIRV_OPT $OUT_UNOPTIMIZED/faded-bfrag.spv -o $OUT_OPTIMIZED/faded-bfrag.spv
 $SPIRV_OPT $OUT_UNOPTIMIZED/faded-vert.spv -o $OUT_OPTIMIZED/faded-vert.spv
 
 glslangValidator $SRC/tinted-frag.glsl -V -o $OUT_UNOPTIMIZED/tinted-frag.spv -S frag
+glslangValidator $SRC/tinted-bfrag.glsl -V -o $OUT_UNOPTIMIZED/tinted-bfrag.spv -S frag
 glslangValidator $SRC/tinted-vert.glsl -V -o $OUT_UNOPTIMIZED/tinted-vert.spv -S vert
 $SPIRV_OPT $OUT_UNOPTIMIZED/tinted-frag.spv -o $OUT_OPTIMIZED/tinted-frag.spv
+$SPIRV_OPT $OUT_UNOPTIMIZED/tinted-bfrag.spv -o $OUT_OPTIMIZED/tinted-bfrag.spv
 $SPIRV_OPT $OUT_UNOPTIMIZED/tinted-vert.spv -o $OUT_OPTIMIZED/tinted-vert.spv
 
 glslangValidator $SRC/complex-frag.glsl -V -o $OUT_UNOPTIMIZED/complex-frag.spv -S frag
+glslangValidator $SRC/complex-bfrag.glsl -V -o $OUT_UNOPTIMIZED/complex-bfrag.spv -S frag
 glslangValidator $SRC/complex-vert.glsl -V -o $OUT_UNOPTIMIZED/complex-vert.spv -S vert
 $SPIRV_OPT $OUT_UNOPTIMIZED/complex-frag.spv -o $OUT_OPTIMIZED/complex-frag.spv
+$SPIRV_OPT $OUT_UNOPTIMIZED/complex-bfrag.spv -o $OUT_OPTIMIZED/complex-bfrag.spv
 $SPIRV_OPT $OUT_UNOPTIMIZED/complex-vert.spv -o $OUT_OPTIMIZED/complex-vert.spv
 
 spirv-remap --map all --dce all --strip-all --input $OUT_OPTIMIZED/*.spv --output $OUT_RELEASE/
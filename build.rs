@@ -0,0 +1,105 @@
+// Copyright Jeron A. Lau 2018.
+// Dual-licensed under either the MIT License or the Boost Software License,
+// Version 1.0.  (See accompanying file LICENSE_1_0.txt or copy at
+// https://www.boost.org/LICENSE_1_0.txt)
+
+//! Put the SPIR-V for every style in `OUT_DIR`.  With the `shaderc` feature
+//! it's compiled from `src/shaders/glsl`, otherwise the SPIR-V committed in
+//! `src/shaders/res` is used.  Either way it's checked against the uniform,
+//! textures and vertex buffers the renderer gives it before it's embedded,
+//! so a shader that doesn't match fails the build.
+
+#[cfg(feature = "shaderc")]
+extern crate shaderc;
+
+use std::{ env, fs };
+use std::mem::size_of;
+use std::path::Path;
+
+#[allow(dead_code)]
+#[path = "src/renderer/spirv.rs"]
+mod spirv;
+
+#[allow(dead_code)]
+#[path = "src/renderer/uniforms.rs"]
+mod uniforms;
+
+use uniforms::*;
+
+/// The shaders each style is made from, with the size of its uniform and
+/// how many textures and vertex buffers it has (as in `shaders!()` in
+/// `src/renderer/mod.rs`).
+const STYLES: [(&str, usize, u32, u32); 6] = [
+	("solid", size_of::<TransformAndColorUniform>(), 0, 1),
+	("texture", size_of::<TransformFullUniform>(), 1, 2),
+	("gradient", size_of::<TransformFullUniform>(), 0, 2),
+	("faded", size_of::<TransformAndFadeUniform>(), 1, 2),
+	("tinted", size_of::<TransformAndColorUniform>(), 1, 2),
+	("complex", size_of::<TransformFullUniform>(), 1, 3),
+];
+
+/// Shader stages, as file name suffixes.  `bfrag` is the fragment shader for
+/// blended shapes.
+const STAGES: [&str; 3] = [ "vert", "frag", "bfrag" ];
+
+#[cfg(feature = "shaderc")]
+fn spirv(name: &str) -> Vec<u8> {
+	let path = format!("src/shaders/glsl/{}.glsl", name);
+	let source = fs::read_to_string(&path).unwrap_or_else(|e| {
+		panic!("Couldn't read {}: {}", path, e)
+	});
+	let kind = if name.ends_with("-vert") {
+		shaderc::ShaderKind::Vertex
+	} else {
+		shaderc::ShaderKind::Fragment
+	};
+
+	let mut compiler = shaderc::Compiler::new()
+		.expect("Couldn't start shaderc");
+	let mut options = shaderc::CompileOptions::new()
+		.expect("Couldn't start shaderc");
+	options.set_optimization_level(shaderc::OptimizationLevel::Performance);
+
+	println!("cargo:rerun-if-changed={}", path);
+
+	compiler.compile_into_spirv(&source, kind, &path, "main",
+		Some(&options)).unwrap_or_else(|e| panic!("{}", e))
+		.as_binary_u8().to_vec()
+}
+
+#[cfg(not(feature = "shaderc"))]
+fn spirv(name: &str) -> Vec<u8> {
+	let path = format!("src/shaders/res/{}.spv", name);
+
+	println!("cargo:rerun-if-changed={}", path);
+
+	fs::read(&path).unwrap_or_else(|e| {
+		panic!("Couldn't read {}: {}", path, e)
+	})
+}
+
+fn main() {
+	let out_dir = env::var("OUT_DIR").unwrap();
+
+	for &(style, uniform, textures, vertex_buffers) in STYLES.iter() {
+		let stages = STAGES.iter().map(|stage| {
+			spirv(&format!("{}-{}", style, stage))
+		}).collect::<Vec<_>>();
+
+		// The same check the renderer makes, so it never fails there.
+		for frag in stages[1..].iter() {
+			if let Err(e) = spirv::check(style, &stages[0], frag,
+				uniform, textures, vertex_buffers)
+			{
+				panic!("{}", e);
+			}
+		}
+
+		for (stage, spirv) in STAGES.iter().zip(stages) {
+			let name = format!("{}-{}.spv", style, stage);
+
+			fs::write(Path::new(&out_dir).join(name), spirv)
+				.unwrap();
+		}
+	}
+}
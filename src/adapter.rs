@@ -0,0 +1,167 @@
+// Copyright Jeron A. Lau 2018.
+// Dual-licensed under either the MIT License or the Boost Software License,
+// Version 1.0.  (See accompanying file LICENSE_1_0.txt or copy at
+// https://www.boost.org/LICENSE_1_0.txt)
+
+//! Listing GPUs, and choosing which one a `Display` uses.
+
+use std::env;
+use std::str::FromStr;
+
+use asi_vulkan;
+use asi_vulkan::PhysicalDeviceType;
+
+use Error;
+
+/// The environment variable `AdapterChoice::Env` reads.
+pub const ADAPTER_VAR: &str = "ADI_GPU_ADAPTER";
+
+/// What kind of GPU an `Adapter` is.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AdapterKind {
+	/// A separate GPU, usually the fastest.
+	Discrete,
+	/// A GPU built into the CPU, usually uses less power.
+	Integrated,
+	/// A GPU in a virtual machine.
+	Virtual,
+	/// Rendering on the CPU, such as lavapipe or SwiftShader.
+	Software,
+	Other,
+}
+
+/// Optional features an `Adapter` supports.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Features {
+	/// Anisotropic texture filtering.
+	pub sampler_anisotropy: bool,
+	/// Shading every sample when multisampling, not just every pixel.
+	pub sample_rate_shading: bool,
+	/// BC (DXT) compressed textures.
+	pub texture_compression_bc: bool,
+}
+
+/// A GPU (or software renderer) that can be drawn with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Adapter {
+	/// The name the driver gives it.
+	pub name: String,
+	pub kind: AdapterKind,
+	/// PCI vendor ID.
+	pub vendor: u32,
+	/// Device ID, unique for the vendor.
+	pub device: u32,
+	/// The size in bytes of each memory heap.
+	pub memory_heaps: Vec<u64>,
+	pub features: Features,
+}
+
+/// Which adapter a `Display` uses.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AdapterChoice {
+	/// Let the driver pick.
+	Default,
+	/// The adapter at this index in `adapters()`.
+	Index(usize),
+	/// The first adapter of this kind, or the default one if there isn't
+	/// one.
+	Prefer(AdapterKind),
+	/// Read the choice from the `ADI_GPU_ADAPTER` environment variable: an
+	/// index, or `discrete`, `integrated`, `virtual` or `software`.  Uses
+	/// the default adapter if it's not set.
+	Env,
+}
+
+impl AdapterChoice {
+	/// Pick one of `adapters`, returning its index, or `None` for the
+	/// default one.  Fails if an index is out of range, or the environment
+	/// variable can't be read.
+	pub fn select(&self, adapters: &[Adapter])
+		-> Result<Option<usize>, Error>
+	{
+		match *self {
+			AdapterChoice::Default => Ok(None),
+			AdapterChoice::Index(index) => {
+				if index < adapters.len() {
+					Ok(Some(index))
+				} else {
+					Err(Error::Init(format!("There's no \
+						adapter {}, only {}", index,
+						adapters.len())))
+				}
+			}
+			AdapterChoice::Prefer(kind) => Ok(adapters.iter()
+				.position(|adapter| adapter.kind == kind)),
+			AdapterChoice::Env => match env::var(ADAPTER_VAR) {
+				Ok(choice) => {
+					choice.parse::<AdapterChoice>()?
+						.select(adapters)
+				}
+				Err(env::VarError::NotPresent) => Ok(None),
+				Err(e) => Err(Error::Init(format!("{}: {}",
+					ADAPTER_VAR, e))),
+			},
+		}
+	}
+}
+
+impl FromStr for AdapterChoice {
+	type Err = Error;
+
+	/// Parse an index, or `discrete`, `integrated`, `virtual` or
+	/// `software`.
+	fn from_str(choice: &str) -> Result<AdapterChoice, Error> {
+		let choice = choice.trim().to_lowercase();
+		let kind = match choice.as_str() {
+			"discrete" => AdapterKind::Discrete,
+			"integrated" => AdapterKind::Integrated,
+			"virtual" => AdapterKind::Virtual,
+			"software" => AdapterKind::Software,
+			_ => return choice.parse().map(AdapterChoice::Index)
+				.map_err(|_| Error::Init(format!("{} isn't an \
+					adapter index or kind", choice))),
+		};
+
+		Ok(AdapterChoice::Prefer(kind))
+	}
+}
+
+/// List the adapters that can be drawn with.
+pub fn adapters() -> Result<Vec<Adapter>, Error> {
+	let devices = asi_vulkan::physical_devices().map_err(Error::Init)?;
+
+	Ok(devices.into_iter().map(|device| Adapter {
+		name: device.name,
+		kind: match device.device_type {
+			PhysicalDeviceType::DiscreteGpu => {
+				AdapterKind::Discrete
+			}
+			PhysicalDeviceType::IntegratedGpu => {
+				AdapterKind::Integrated
+			}
+			PhysicalDeviceType::VirtualGpu => AdapterKind::Virtual,
+			PhysicalDeviceType::Cpu => AdapterKind::Software,
+			PhysicalDeviceType::Other => AdapterKind::Other,
+		},
+		vendor: device.vendor_id,
+		device: device.device_id,
+		memory_heaps: device.memory_heaps,
+		features: Features {
+			sampler_anisotropy: device.sampler_anisotropy,
+			sample_rate_shading: device.sample_rate_shading,
+			texture_compression_bc: device.texture_compression_bc,
+		},
+	}).collect())
+}
+
+/// Get the index of the adapter `choice` picks, without listing the adapters
+/// if it doesn't need to.
+pub(crate) fn select(choice: &AdapterChoice) -> Result<Option<usize>, Error> {
+	match *choice {
+		AdapterChoice::Default => Ok(None),
+		AdapterChoice::Env if env::var_os(ADAPTER_VAR).is_none() => {
+			Ok(None)
+		}
+		ref choice => choice.select(&adapters()?),
+	}
+}
@@ -0,0 +1,81 @@
+// Copyright Jeron A. Lau 2018.
+// Dual-licensed under either the MIT License or the Boost Software License,
+// Version 1.0.  (See accompanying file LICENSE_1_0.txt or copy at
+// https://www.boost.org/LICENSE_1_0.txt)
+
+//! Validation layer messages.
+
+use asi_vulkan;
+use asi_vulkan::DebugSeverity;
+
+/// How bad a validation message is.
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+pub enum Severity {
+	Verbose,
+	Info,
+	Warning,
+	Error,
+}
+
+/// Whether to turn on validation, and where its messages go.
+#[derive(Copy, Clone, Debug)]
+pub enum DebugMode {
+	/// No validation (the default).
+	Off,
+	/// Send messages to the `log` crate with the `log` feature, or print
+	/// them to stderr without it.
+	Log,
+	/// Send messages to a function.
+	Callback(fn(Severity, &str)),
+}
+
+impl PartialEq for DebugMode {
+	fn eq(&self, other: &DebugMode) -> bool {
+		match (*self, *other) {
+			(DebugMode::Off, DebugMode::Off) => true,
+			(DebugMode::Log, DebugMode::Log) => true,
+			// The same if they're at the same address.
+			(DebugMode::Callback(a), DebugMode::Callback(b)) => {
+				a as usize == b as usize
+			}
+			_ => false,
+		}
+	}
+}
+
+/// Make the messenger callback for `mode`, or `None` if it's off.
+pub(crate) fn callback(mode: DebugMode)
+	-> Option<asi_vulkan::DebugCallback>
+{
+	let callback: fn(Severity, &str) = match mode {
+		DebugMode::Off => return None,
+		DebugMode::Log => log,
+		DebugMode::Callback(callback) => callback,
+	};
+
+	Some(Box::new(move |severity, message| {
+		callback(match severity {
+			DebugSeverity::Verbose => Severity::Verbose,
+			DebugSeverity::Info => Severity::Info,
+			DebugSeverity::Warning => Severity::Warning,
+			DebugSeverity::Error => Severity::Error,
+		}, message)
+	}))
+}
+
+#[cfg(feature = "log")]
+fn log(severity: Severity, message: &str) {
+	let level = match severity {
+		Severity::Verbose => ::log::Level::Debug,
+		Severity::Info => ::log::Level::Info,
+		Severity::Warning => ::log::Level::Warn,
+		Severity::Error => ::log::Level::Error,
+	};
+
+	log!(target: "vulkan", level, "{}", message);
+}
+
+#[cfg(not(feature = "log"))]
+fn log(severity: Severity, message: &str) {
+	eprintln!("Vulkan {:?}: {}", severity, message);
+}
@@ -10,9 +10,15 @@
 extern crate asi_vulkan;
 extern crate adi_gpu_base;
 extern crate libc;
+#[cfg(feature = "log")]
+#[macro_use]
+extern crate log;
 
 /// Transform represents a transformation matrix.
 pub(crate) mod renderer;
+mod adapter;
+mod debug;
+mod sampler;
 
 pub use base::Shape;
 pub use base::Gradient;
@@ -20,36 +26,484 @@ pub use base::Model;
 pub use base::TexCoords;
 pub use base::Texture;
 
+pub use renderer::Device;
+pub use renderer::Call;
+pub use renderer::Recorder;
+pub use renderer::HandleError;
+pub use renderer::Error;
+pub use adapter::{ adapters, Adapter, AdapterChoice, AdapterKind, Features };
+pub use adapter::ADAPTER_VAR;
+pub use debug::{ DebugMode, Severity };
+pub use sampler::{ AddressMode, BorderColor, Filter, SamplerDesc };
+
 use adi_gpu_base as base;
 use adi_gpu_base::*;
 
+use asi_vulkan::Gpu;
+
+use std::marker::PhantomData;
+use std::path::PathBuf;
+
+/// How rendered images are shown on the screen.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PresentMode {
+	/// Wait for vertical blank (vsync), never tears.  Always supported.
+	Fifo,
+	/// Replace the queued image on every frame, doesn't tear and has
+	/// less latency than `Fifo`.  Falls back to `Fifo`.
+	Mailbox,
+	/// Show images as soon as they're rendered, may tear.  Falls back to
+	/// `Mailbox`, then `Fifo`.
+	Immediate,
+}
+
+/// Settings for a `Display`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DisplayConfig {
+	/// The present mode to try first.
+	pub present_mode: PresentMode,
+	/// How many swapchain images to ask for, from 2 to 4.  Clamped to what
+	/// the surface supports.
+	pub image_count: u32,
+	/// How many samples per pixel to multisample (antialias) with: 1 (off),
+	/// 2, 4 or 8.  Lowered to what the GPU supports.  The default is 8.
+	pub samples: u32,
+	/// A directory to keep the pipeline cache in, so styles are made faster
+	/// on the next run.  A cache saved by another GPU or driver is
+	/// ignored.  `None` (the default) doesn't keep one.
+	pub pipeline_cache: Option<PathBuf>,
+	/// Which GPU to use.  The default is `AdapterChoice::Env`.
+	pub adapter: AdapterChoice,
+	/// Turn on the validation layer (if it's installed) and name GPU
+	/// objects after their handles, so its messages say which one they're
+	/// about.  Slow, so `Off` by default.
+	pub debug: DebugMode,
+}
+
+impl Default for DisplayConfig {
+	fn default() -> DisplayConfig {
+		DisplayConfig {
+			present_mode: PresentMode::Fifo,
+			image_count: 2,
+			samples: 8,
+			pipeline_cache: None,
+			adapter: AdapterChoice::Env,
+			debug: DebugMode::Off,
+		}
+	}
+}
+
+/// What a texture's pixels are.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ColorSpace {
+	/// Colors, stored in sRGB (like almost every image), which are turned
+	/// into linear values when sampled.
+	Srgb,
+	/// Data that's sampled as stored, like normal maps and masks.
+	Linear,
+}
+
+impl Default for ColorSpace {
+	fn default() -> ColorSpace {
+		ColorSpace::Srgb
+	}
+}
+
+/// How a texture's pixels are stored.  Pixels are passed in as bytes, with
+/// each channel native-endian for the float formats.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TextureFormat {
+	/// 8-bit red, green, blue and alpha (the default).
+	Rgba8,
+	/// One 8-bit channel, like a font's coverage mask.
+	R8,
+	/// Two 8-bit channels.
+	Rg8,
+	/// Four 16-bit (half) float channels, for HDR colors.
+	Rgba16F,
+	/// Four 32-bit float channels.
+	Rgba32F,
+	/// One 32-bit float depth value.  Uploaded through a buffer, since
+	/// depth can only be sampled from optimal tiled images.
+	Depth32F,
+}
+
+impl TextureFormat {
+	/// Get the size of a pixel in bytes.
+	pub fn bytes_per_pixel(self) -> usize {
+		match self {
+			TextureFormat::Rgba8 => 4,
+			TextureFormat::R8 => 1,
+			TextureFormat::Rg8 => 2,
+			TextureFormat::Rgba16F => 8,
+			TextureFormat::Rgba32F => 16,
+			TextureFormat::Depth32F => 4,
+		}
+	}
+}
+
+impl Default for TextureFormat {
+	fn default() -> TextureFormat {
+		TextureFormat::Rgba8
+	}
+}
+
+/// Settings for a texture made with `Display::texture_with_config()`.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct TextureConfig {
+	/// Make a full chain of mip levels, so the texture doesn't shimmer when
+	/// it's drawn smaller than it is.  They're made again whenever the
+	/// pixels are set.  Off by default.
+	pub mipmaps: bool,
+	/// How shapes sample the texture.
+	pub sampler: SamplerDesc,
+	/// What the pixels are.  The default is `ColorSpace::Srgb`.  Only
+	/// `TextureFormat::Rgba8` textures can be sRGB, the others are always
+	/// linear.
+	pub color_space: ColorSpace,
+	/// How the pixels are stored.  Fails with `Error::UnsupportedFormat` if
+	/// the GPU can't sample it.
+	pub format: TextureFormat,
+}
+
+/// A style made from your own shaders with `Display::style()`.  `U` is the
+/// type of the shaders' part of the uniform block.
+pub struct Style<U> {
+	handle: u32,
+	uniform: PhantomData<U>,
+}
+
+impl<U> Clone for Style<U> {
+	fn clone(&self) -> Style<U> {
+		*self
+	}
+}
+
+impl<U> Copy for Style<U> { }
+
 /// To render anything with adi_gpu, you have to make a `Display`
-pub struct Display {
-	window: adi_gpu_base::Window,
-	renderer: renderer::Renderer,
+pub struct Display<D: Device = Gpu> {
+	window: Option<adi_gpu_base::Window>,
+	wh: (u16, u16),
+	renderer: renderer::Renderer<D>,
+	device_lost: bool, // Whether the GPU was lost since `device_lost()`
+	error: Option<Error>, // The last error since `last_error()`
+}
+
+pub fn new(title: &str, icon: &afi::Video) -> Result<Box<Display>, Error> {
+	with_config(title, icon, DisplayConfig::default())
 }
 
-pub fn new(title: &str, icon: &afi::Video) -> Result<Box<Display>, String> {
+/// Like `new()`, but with settings.
+pub fn with_config(title: &str, icon: &afi::Video, config: DisplayConfig)
+	-> Result<Box<Display>, Error>
+{
 	let (renderer, window) = renderer::Renderer::new(
 		Some((title, icon)),
-		vec3!(0.0, 0.0, 0.0)
+		vec3!(0.0, 0.0, 0.0),
+		config,
 	)?;
+	let wh = window.wh();
+
+	Ok(Box::new(Display {
+		window: Some(window), wh, renderer, device_lost: false,
+		error: None,
+	}))
+}
+
+/// Make a `Display` without a window, that renders into an offscreen image of
+/// `width` by `height` pixels.  Works with software Vulkan implementations,
+/// such as lavapipe.
+pub fn new_headless(width: u16, height: u16) -> Result<Box<Display>, Error> {
+	headless_with_config(width, height, DisplayConfig::default())
+}
+
+/// Like `new_headless()`, but with settings.  Only the adapter, pipeline
+/// cache, debug and samples settings are used.
+pub fn headless_with_config(width: u16, height: u16, config: DisplayConfig)
+	-> Result<Box<Display>, Error>
+{
+	let renderer = renderer::Renderer::new_headless(width, height,
+		vec3!(0.0, 0.0, 0.0), config)?;
+
+	Ok(Box::new(Display {
+		window: None, wh: (width, height), renderer, device_lost: false,
+		error: None,
+	}))
+}
+
+/// Make a `Display` without a window that draws with `device`, for example a
+/// `Recorder`.
+pub fn with_device<D: Device>(device: D, wh: (u16, u16), config: DisplayConfig)
+	-> Result<Box<Display<D>>, Error>
+{
+	let renderer = renderer::Renderer::with_device(device,
+		vec3!(0.0, 0.0, 0.0), wh.0 as f32 / wh.1 as f32, None, config)?;
 
-	Ok(Box::new(Display { window, renderer }))
+	Ok(Box::new(Display {
+		window: None, wh, renderer, device_lost: false,
+		error: None,
+	}))
 }
 
-impl base::Display for Display {
+/// Like `with_device()`, but renders into an offscreen image, like
+/// `new_headless()`.
+pub fn headless_with_device<D: Device>(device: D, wh: (u16, u16),
+	config: DisplayConfig) -> Result<Box<Display<D>>, Error>
+{
+	let renderer = renderer::Renderer::with_device(device,
+		vec3!(0.0, 0.0, 0.0), wh.0 as f32 / wh.1 as f32, Some(wh),
+		config)?;
+
+	Ok(Box::new(Display {
+		window: None, wh, renderer, device_lost: false,
+		error: None,
+	}))
+}
+
+impl<D: Device> Display<D> {
+	/// Get the device this `Display` draws with.
+	pub fn device(&self) -> &D {
+		self.renderer.device()
+	}
+
+	/// Get the device this `Display` draws with, mutably.
+	pub fn device_mut(&mut self) -> &mut D {
+		self.renderer.device_mut()
+	}
+
+	/// Free a `Model`.  Fails with `HandleError::InUse` until shapes made
+	/// from it are dropped.
+	pub fn drop_model(&mut self, model: &Model) -> Result<(), HandleError> {
+		self.renderer.drop_model(model.0)
+	}
+
+	/// Free a `Texture`.  Fails with `HandleError::InUse` until shapes made
+	/// from it are dropped.
+	pub fn drop_texture(&mut self, texture: &Texture)
+		-> Result<(), HandleError>
+	{
+		self.renderer.drop_texture(texture.0)
+	}
+
+	/// Free `TexCoords`.  Fails with `HandleError::InUse` until shapes made
+	/// from them are dropped.
+	pub fn drop_texcoords(&mut self, texcoords: &TexCoords)
+		-> Result<(), HandleError>
+	{
+		self.renderer.drop_texcoords(texcoords.0)
+	}
+
+	/// Free a `Gradient`.  Fails with `HandleError::InUse` until shapes
+	/// made from it are dropped.
+	pub fn drop_gradient(&mut self, gradient: &Gradient)
+		-> Result<(), HandleError>
+	{
+		self.renderer.drop_gradient(gradient.0)
+	}
+
+	/// Set the layer of a GUI shape (one made without the camera or fog).
+	/// Shapes on higher layers are drawn on top, and shapes on the same
+	/// layer are drawn in the order they were made.  The default layer is 0.
+	pub fn gui_layer(&mut self, shape: &Shape, layer: i32)
+		-> Result<(), HandleError>
+	{
+		self.renderer.gui_layer(&get_shape(shape), layer)
+	}
+
+	/// Check that `shape` hasn't been dropped, and belongs to this
+	/// `Display`.  Shapes that fail this check are ignored by `drop_shape()`
+	/// and `transform()` (which keep the error for `last_error()`), and
+	/// shapes that failed to be made (because of a bad `Model`, `Texture`,
+	/// etc.) fail it too.
+	pub fn check_shape(&self, shape: &Shape) -> Result<(), HandleError> {
+		self.renderer.check_shape(&get_shape(shape))
+	}
+
+	/// Make a style from SPIR-V shaders.  The uniform block at binding 0
+	/// starts with the renderer's part, and `U` follows at offset 80:
+	///
+	/// ```glsl
+	/// layout (binding = 0) uniform UniformBuffer {
+	/// 	mat4 models_tfm; // The Models' Transform Matrix
+	/// 	int has_camera;
+	/// 	layout (offset = 80) vec4 wave; // `U` is [f32; 4]
+	/// } uniforms;
+	/// ```
+	///
+	/// Bindings 1 and 2 are the camera and fog, like in the built-in
+	/// shaders, and binding 3 is the texture if `textures` is 1.  There
+	/// are 1 to 3 `vertex_buffers`: the model, then texcoords and/or a
+	/// gradient.  The same fragment shader is used with and without
+	/// blending.  Returns an error if the shaders don't match.
+	pub fn style<U: Copy + 'static>(&mut self, vert: &[u8], frag: &[u8],
+		textures: u32, vertex_buffers: u32) -> Result<Style<U>, Error>
+	{
+		let handle = self.renderer.style::<U>(vert, frag, textures,
+			vertex_buffers)?;
+
+		Ok(Style { handle, uniform: PhantomData })
+	}
+
+	/// Make a shape with a custom `style`.  `texture`, `tc` and `colors`
+	/// must be `Some` for the textures and vertex buffers the style has.
+	/// Fails if they don't match the style, or the model.
+	pub fn shape_custom<U: Copy + 'static>(&mut self, style: &Style<U>,
+		model: &Model, transform: Transform, uniform: U,
+		texture: Option<&Texture>, tc: Option<&TexCoords>,
+		colors: Option<&Gradient>, blending: bool, fog: bool,
+		camera: bool) -> Result<Shape, Error>
+	{
+		Ok(base::new_shape(self.renderer.custom(style.handle, model.0,
+			transform, uniform, texture.map(|t| t.0),
+			tc.map(|tc| tc.0), colors.map(|c| c.0), blending, fog,
+			camera)?))
+	}
+
+	/// Change the uniform of a shape made with a custom `style`.
+	pub fn set_uniform<U: Copy + 'static>(&mut self, shape: &Shape,
+		style: &Style<U>, uniform: U) -> Result<(), Error>
+	{
+		self.renderer.set_uniform(&get_shape(shape), style.handle,
+			uniform)
+	}
+
+	/// Check whether the GPU was lost (reset, removed or crashed) since the
+	/// last call.  `update()` makes everything again on a new one from
+	/// copies kept in memory, so nothing has to be remade by hand.
+	pub fn device_lost(&mut self) -> bool {
+		::std::mem::replace(&mut self.device_lost, false)
+	}
+
+	/// Get the last error from a method that can't return one (like
+	/// `update()`, `resize()`, `set_texture()` or the `shape_*()` methods)
+	/// since the last call, if there was one.
+	pub fn last_error(&mut self) -> Option<Error> {
+		self.error.take()
+	}
+
+	/// Get the present mode that was actually selected, which may not be
+	/// the one asked for.
+	pub fn present_mode(&self) -> PresentMode {
+		self.renderer.present_mode()
+	}
+
+	/// Get the number of swapchain images (1 when headless).
+	pub fn image_count(&self) -> u32 {
+		self.renderer.image_count()
+	}
+
+	/// Make a texture with `config`, instead of the default settings that
+	/// `texture()` uses.  `graphic` holds pixels of `config.format`.  Fails
+	/// with `Error::UnsupportedFormat` if the GPU can't use `config`.
+	pub fn texture_with_config(&mut self, wh: (u16, u16), graphic: &VFrame,
+		config: TextureConfig) -> Result<Texture, Error>
+	{
+		let (w, h) = wh;
+		let pixels = graphic.0.as_slice();
+
+		Ok(Texture(self.renderer.texture(w, h, pixels, config)?, w, h))
+	}
+
+	/// Replace the `w` by `h` rectangle of a texture with its top left
+	/// corner at `(x, y)`.  Only that rectangle is uploaded, so it's faster
+	/// than `set_texture()` for small changes.  An empty rectangle does
+	/// nothing.
+	pub fn set_texture_region(&mut self, texture: &Texture, x: u16, y: u16,
+		w: u16, h: u16, graphic: &VFrame) -> Result<(), Error>
+	{
+		self.renderer.set_texture_region(texture.0, x, y, w, h,
+			graphic.0.as_slice())
+	}
+
+	/// Get the number of samples per pixel that was actually selected,
+	/// which may be less than the one asked for.
+	pub fn samples(&self) -> u32 {
+		self.renderer.samples()
+	}
+
+	/// Set how many samples per pixel to multisample with: 1 (off), 2, 4
+	/// or 8.  Lowered to what the GPU supports.
+	pub fn set_samples(&mut self, samples: u32) -> Result<(), Error> {
+		let wh = base::Display::wh(self);
+
+		self.renderer.set_samples(samples, wh)
+	}
+
+	/// Set how many frames may be rendering on the GPU while the next one
+	/// is built, either 2 or 3.  The default is 2.
+	pub fn frames_in_flight(&mut self, count: usize) {
+		self.renderer.frames_in_flight(count);
+	}
+
+	/// Copy the last image rendered by `update()` into an RGBA `VFrame`.
+	/// Returns `None` if nothing has been rendered yet, or since the
+	/// display was resized.
+	pub fn screenshot(&mut self) -> Result<Option<VFrame>, Error> {
+		let wh = base::Display::wh(self);
+
+		Ok(self.renderer.screenshot(wh)?.map(|rgba| VFrame(rgba)))
+	}
+
+	/// Keep the error of `result` (if any) for `last_error()`.
+	fn report<T, E: Into<Error>>(&mut self, result: Result<T, E>)
+		-> Option<T>
+	{
+		match result {
+			Ok(value) => Some(value),
+			Err(e) => {
+				self.error = Some(e.into());
+				None
+			}
+		}
+	}
+
+	/// Make a `Shape` from a handle.  A shape that couldn't be made gets
+	/// the null handle (which is never valid) of the kind it would have
+	/// been, and the error is kept for `last_error()`.
+	fn shape(&mut self, handle: Result<ShapeHandle, Error>, alpha: bool,
+		fog: bool, camera: bool) -> Shape
+	{
+		let null = renderer::shape_kind(alpha, fog, camera)(0);
+
+		base::new_shape(self.report(handle).unwrap_or(null))
+	}
+}
+
+impl<D: Device> base::Display for Display<D> {
 	fn color(&mut self, color: (f32, f32, f32)) {
 		self.renderer.bg_color(vec3!(color.0, color.1, color.2));
 	}
 
 	fn update(&mut self) -> Option<adi_gpu_base::Input> {
-		if let Some(input) = self.window.update() {
-			return Some(input);
+		if let Some(ref mut window) = self.window {
+			if let Some(input) = window.update() {
+				return Some(input);
+			}
 		}
 
 		// Update Window:
-		self.renderer.update();
+		match self.renderer.update() {
+			Ok(()) => {}
+			// The swapchain is out of date, make a new one.
+			Err(Error::SurfaceLost) => {
+				let wh = base::Display::wh(self);
+				let resized = self.renderer.resize(wh);
+
+				self.report(resized);
+			}
+			// Make everything again on a new device, or try again
+			// on the next frame.
+			Err(Error::DeviceLost) => {
+				let wh = base::Display::wh(self);
+				let rebuilt = self.renderer.rebuild(wh);
+
+				if self.report(rebuilt).is_some() {
+					self.device_lost = true;
+				}
+			}
+			Err(e) => self.error = Some(e),
+		}
 		// Return None, there was no input, updated screen.
 		None
 	}
@@ -60,7 +514,11 @@ impl base::Display for Display {
 	}
 
 	fn model(&mut self, vertices: &[f32], fans: Vec<(u32, u32)>) -> Model {
-		Model(self.renderer.model(vertices, fans))
+		// A model that couldn't be made is a null handle, so shapes
+		// made from it fail `check_shape()`.
+		let model = self.renderer.model(vertices, fans);
+
+		Model(self.report(model).unwrap_or(0))
 	}
 
 	fn fog(&mut self, fog: Option<(f32, f32)>) -> () {
@@ -72,31 +530,41 @@ impl base::Display for Display {
 	}
 
 	fn texture(&mut self, wh: (u16,u16), graphic: &VFrame) -> Texture {
-		let (w, h) = wh;
-		let pixels = graphic.0.as_slice();
+		// A texture that couldn't be made is a null handle, so shapes
+		// made from it fail `check_shape()`.
+		let texture = self.texture_with_config(wh, graphic,
+			TextureConfig::default());
 
-		Texture(self.renderer.texture(w, h, pixels), wh.0, wh.1)
+		self.report(texture).unwrap_or(Texture(0, wh.0, wh.1))
 	}
 
 	fn gradient(&mut self, colors: &[f32]) -> Gradient {
-		Gradient(self.renderer.colors(colors))
+		let gradient = self.renderer.colors(colors);
+
+		Gradient(self.report(gradient).unwrap_or(0))
 	}
 
 	fn texcoords(&mut self, texcoords: &[f32]) -> TexCoords {
-		TexCoords(self.renderer.texcoords(texcoords))
+		let texcoords = self.renderer.texcoords(texcoords);
+
+		TexCoords(self.report(texcoords).unwrap_or(0))
 	}
 
 	fn set_texture(&mut self, texture: &mut Texture, wh: (u16,u16),
 		graphic: &VFrame)
 	{
-		if texture.1 == wh.0 && texture.2 == wh.1 {
+		// A dropped texture has nothing to set, and one that can't be
+		// set keeps its old pixels.
+		let set = if texture.1 == wh.0 && texture.2 == wh.1 {
 			self.renderer.set_texture(texture.0,
-				graphic.0.as_slice());
+				graphic.0.as_slice())
 		} else {
 			// resize
 			self.renderer.resize_texture(texture.0, wh.0, wh.1,
-				graphic.0.as_slice());
-		}
+				graphic.0.as_slice())
+		};
+
+		self.report(set);
 	}
 
 	#[inline(always)]
@@ -104,8 +572,10 @@ impl base::Display for Display {
 		color: [f32; 4], blending: bool, fog: bool,
 		camera: bool) -> Shape
 	{
-		base::new_shape(self.renderer.solid(model.0, transform, color,
-			blending, fog, camera))
+		let handle = self.renderer.solid(model.0, transform, color,
+			blending, fog, camera);
+
+		self.shape(handle, blending, fog, camera)
 	}
 
 	#[inline(always)]
@@ -113,8 +583,10 @@ impl base::Display for Display {
 		colors: Gradient, blending: bool, fog: bool,
 		camera: bool) -> Shape
 	{
-		base::new_shape(self.renderer.gradient(model.0, transform,
-			colors.0, blending, fog, camera))
+		let handle = self.renderer.gradient(model.0, transform,
+			colors.0, blending, fog, camera);
+
+		self.shape(handle, blending, fog, camera)
 	}
 
 	#[inline(always)]
@@ -122,8 +594,10 @@ impl base::Display for Display {
 		texture: &Texture, tc: TexCoords, blending: bool,
 		fog: bool, camera: bool) -> Shape
 	{
-		base::new_shape(self.renderer.textured(model.0, transform,
-			texture.0, tc.0, blending, fog, camera))
+		let handle = self.renderer.textured(model.0, transform,
+			texture.0, tc.0, blending, fog, camera);
+
+		self.shape(handle, blending, fog, camera)
 	}
 
 	#[inline(always)]
@@ -131,8 +605,10 @@ impl base::Display for Display {
 		texture: &Texture, tc: TexCoords, alpha: f32,
 		fog: bool, camera: bool) -> Shape
 	{
-		base::new_shape(self.renderer.faded(model.0, transform,
-			texture.0, tc.0, alpha, fog, camera))
+		let handle = self.renderer.faded(model.0, transform,
+			texture.0, tc.0, alpha, fog, camera);
+
+		self.shape(handle, true, fog, camera)
 	}
 
 	#[inline(always)]
@@ -140,8 +616,10 @@ impl base::Display for Display {
 		texture: &Texture, tc: TexCoords, tint: [f32; 4], blending: bool,
 		fog: bool, camera: bool) -> Shape
 	{
-		base::new_shape(self.renderer.tinted(model.0, transform,
-			texture.0, tc.0, tint, blending, fog, camera))
+		let handle = self.renderer.tinted(model.0, transform,
+			texture.0, tc.0, tint, blending, fog, camera);
+
+		self.shape(handle, blending, fog, camera)
 	}
 
 	#[inline(always)]
@@ -149,24 +627,41 @@ impl base::Display for Display {
 		texture: &Texture, tc: TexCoords, tints: Gradient,
 		blending: bool, fog: bool, camera: bool) -> Shape
 	{
-		base::new_shape(self.renderer.complex(model.0, transform,
-			texture.0, tc.0, tints.0, blending, fog, camera))
+		let handle = self.renderer.complex(model.0, transform,
+			texture.0, tc.0, tints.0, blending, fog, camera);
+
+		self.shape(handle, blending, fog, camera)
 	}
 
 	#[inline(always)]
 	fn drop_shape(&mut self, shape: &Shape) {
-		self.renderer.drop_shape(get_shape(&shape));
+		// Invalid shapes have nothing to drop, see `check_shape()`.
+		let dropped = self.renderer.drop_shape(get_shape(&shape));
+
+		self.report(dropped);
 	}
 
 	fn transform(&mut self, shape: &Shape, transform: Transform) {
-		self.renderer.transform(&base::get_shape(shape), transform);
+		// Invalid shapes have nothing to transform, see `check_shape()`.
+		let shape = base::get_shape(shape);
+		let transformed = self.renderer.transform(&shape, transform);
+
+		self.report(transformed);
 	}
 
 	fn resize(&mut self, wh: (u16, u16)) -> () {
-		self.renderer.resize(wh);
+		self.wh = wh;
+
+		let resized = self.renderer.resize(wh);
+
+		self.report(resized);
 	}
 
 	fn wh(&self) -> (u16, u16) {
-		self.window.wh()
+		if let Some(ref window) = self.window {
+			window.wh()
+		} else {
+			self.wh
+		}
 	}
 }
@@ -0,0 +1,71 @@
+// Copyright Jeron A. Lau 2018.
+// Dual-licensed under either the MIT License or the Boost Software License,
+// Version 1.0.  (See accompanying file LICENSE_1_0.txt or copy at
+// https://www.boost.org/LICENSE_1_0.txt)
+
+//! Saving and loading the pipeline cache.
+
+use std::fs;
+use std::path::{ Path, PathBuf };
+
+/// `VK_PIPELINE_CACHE_HEADER_VERSION_ONE`
+const HEADER_VERSION_ONE: u32 = 1;
+/// Size of a version one header: length, version, vendor ID, device ID and
+/// UUID.
+const HEADER_SIZE: usize = 32;
+
+/// Get the file the pipeline cache is kept in, in `dir`.
+pub fn file(dir: &Path) -> PathBuf {
+	dir.join("pipeline_cache")
+}
+
+/// Make a version one pipeline cache header.
+pub fn header((vendor, device, uuid): (u32, u32, [u8; 16])) -> Vec<u8> {
+	let mut header = Vec::with_capacity(HEADER_SIZE);
+
+	header.extend_from_slice(&(HEADER_SIZE as u32).to_ne_bytes());
+	header.extend_from_slice(&HEADER_VERSION_ONE.to_ne_bytes());
+	header.extend_from_slice(&vendor.to_ne_bytes());
+	header.extend_from_slice(&device.to_ne_bytes());
+	header.extend_from_slice(&uuid);
+	header
+}
+
+fn read_u32(data: &[u8], at: usize) -> u32 {
+	u32::from_ne_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+}
+
+/// Load the pipeline cache in `dir`, if it was saved by the device and driver
+/// with `id` (vendor ID, device ID and pipeline cache UUID).  Returns an
+/// empty cache otherwise, so a stale one is thrown away.
+pub fn load(dir: &Path, id: (u32, u32, [u8; 16])) -> Vec<u8> {
+	let data = match fs::read(file(dir)) {
+		Ok(data) => data,
+		Err(_) => return Vec::new(),
+	};
+
+	if data.len() < HEADER_SIZE
+		|| (read_u32(&data, 0) as usize) < HEADER_SIZE
+		|| read_u32(&data, 4) != HEADER_VERSION_ONE
+		|| data[8..HEADER_SIZE] != header(id)[8..]
+	{
+		return Vec::new();
+	}
+
+	data
+}
+
+/// Save the pipeline cache in `dir`.  It's written to a temporary file that
+/// then replaces the old one, so a crash can't leave half a cache behind.
+/// Failing to is harmless (pipelines are just built from scratch next time),
+/// so errors are ignored.
+pub fn save(dir: &Path, data: &[u8]) {
+	let temp = dir.join("pipeline_cache.tmp");
+	let saved = fs::create_dir_all(dir)
+		.and_then(|_| fs::write(&temp, data))
+		.and_then(|_| fs::rename(&temp, file(dir)));
+
+	if saved.is_err() {
+		let _ = fs::remove_file(&temp);
+	}
+}
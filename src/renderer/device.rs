@@ -0,0 +1,861 @@
+// Copyright Jeron A. Lau 2018.
+// Dual-licensed under either the MIT License or the Boost Software License,
+// Version 1.0.  (See accompanying file LICENSE_1_0.txt or copy at
+// https://www.boost.org/LICENSE_1_0.txt)
+
+use adi_gpu_base::*;
+
+use asi_vulkan;
+use asi_vulkan::types::*;
+use asi_vulkan::Gpu;
+use asi_vulkan::TransformUniform;
+use asi_vulkan::FogUniform;
+
+use PresentMode;
+use { AddressMode, BorderColor, Filter, SamplerDesc };
+
+use super::Error;
+use super::ffi;
+use super::MAX_IMAGES;
+
+/// Everything the `Renderer` needs from a GPU.  `asi_vulkan::Gpu` is the
+/// real implementation, `Recorder` is an in-memory one for testing.
+pub trait Device {
+	/// An image in GPU memory.
+	type Image: Clone;
+	/// A vertex buffer in GPU memory.  The default is a null buffer, left
+	/// behind when the device is lost.
+	type Buffer: Default;
+	/// Raw handle of a `Buffer`, for binding.  The default is a null
+	/// handle, for unused slots.
+	type VertexBuffer: Copy + Default;
+	/// A loaded SPIR-V module.
+	type Shader;
+	/// A graphics pipeline.
+	type Style;
+	/// Per-shape uniform buffer and descriptor set.
+	type Sprite;
+	/// Uniform memory for the camera matrix.
+	type CameraMemory;
+	/// Uniform memory for the fog.
+	type EffectMemory;
+	type RenderPass: Copy;
+	// The defaults are null handles, for swapchain slots not in use.
+	type Framebuffer: Copy + Default;
+	type PresentImage: Copy + Default;
+	type ImageView: Copy + Default;
+	type Fence;
+	type Semaphore;
+	type CommandBuffer;
+	/// A pipeline cache, which styles are made with.
+	type PipelineCache: Copy;
+	/// How a texture is sampled.
+	type Sampler: Copy;
+
+	/// Whether linear tiled images can be sampled directly.
+	fn sampled(&self) -> bool;
+	/// Set the clear color.
+	fn color(&mut self, rgb: Vec3);
+	/// Get the present modes the surface supports.
+	fn present_modes(&mut self) -> Vec<PresentMode>;
+	/// Get the minimum and maximum number of swapchain images the surface
+	/// supports.  A maximum of 0 means there's no limit.
+	fn image_counts(&mut self) -> (u32, u32);
+	/// Get the format of the images that are rendered into.
+	fn present_format(&self) -> VkFormat;
+	/// Get the sample counts both color and depth framebuffers support, as
+	/// `VkSampleCountFlags` (bit 0 for 1 sample, bit 1 for 2, and so on).
+	fn sample_counts(&self) -> u32;
+
+	/// Create the swapchain, depth buffer, multisampling buffer (unless
+	/// `samples` is 1), render pass and framebuffers.  `image_count` is set
+	/// to the number of images the swapchain actually has.  Returns
+	/// `(depth_image, ms_image, render_pass)`.
+	fn swapchain_new(&mut self, present_mode: PresentMode,
+		image_count: &mut u32, samples: VkSampleCount,
+		present_images: &mut [Self::PresentImage; MAX_IMAGES],
+		present_image_views: &mut [Self::ImageView; MAX_IMAGES],
+		frame_buffers: &mut [Self::Framebuffer; MAX_IMAGES])
+		-> (Self::Image, Option<Self::Image>, Self::RenderPass);
+	fn swapchain_drop(&mut self, frame_buffers: &[Self::Framebuffer; MAX_IMAGES],
+		present_image_views: &[Self::ImageView; MAX_IMAGES],
+		render_pass: Self::RenderPass, image_count: u32);
+	/// Create an offscreen color image to render into instead of a
+	/// swapchain, with its depth buffer, multisampling buffer (unless
+	/// `samples` is 1), render pass and framebuffer.  Only the first
+	/// element of each array is used.  Returns `(color_image, depth_image,
+	/// ms_image, render_pass)`.
+	fn offscreen_new(&mut self, width: u16, height: u16,
+		samples: VkSampleCount,
+		present_images: &mut [Self::PresentImage; MAX_IMAGES],
+		present_image_views: &mut [Self::ImageView; MAX_IMAGES],
+		frame_buffers: &mut [Self::Framebuffer; MAX_IMAGES])
+		-> (Self::Image, Self::Image, Option<Self::Image>,
+			Self::RenderPass);
+	fn offscreen_drop(&mut self, frame_buffers: &[Self::Framebuffer; MAX_IMAGES],
+		present_image_views: &[Self::ImageView; MAX_IMAGES],
+		render_pass: Self::RenderPass);
+
+	/// Create an image with `levels` mip levels.
+	fn image(&mut self, width: u32, height: u32, levels: u32,
+		format: VkFormat, tiling: VkImageTiling, usage: VkImageUsage,
+		layout: VkImageLayout, properties: u32, samples: VkSampleCount)
+		-> Result<Self::Image, Error>;
+	/// Get the row pitch (in bytes) of a linear tiled image.
+	fn image_pitch(&mut self, image: &Self::Image) -> u32;
+	/// Write pixels of `bpp` bytes each into a mappable image.
+	fn image_write(&mut self, image: &Self::Image, pixels: &[u8],
+		width: usize, height: usize, pitch: usize, bpp: usize)
+		-> Result<(), Error>;
+	/// Write pixels of `bpp` bytes each into a `size` rectangle of a
+	/// mappable image, with its top left corner at `position`.
+	fn image_write_region(&mut self, image: &Self::Image, pixels: &[u8],
+		position: (u16, u16), size: (u16, u16), pitch: usize,
+		bpp: usize) -> Result<(), Error>;
+	/// Copy 32-bit float depth values into a rectangle of level 0 of an
+	/// optimal tiled depth image, through a staging buffer (depth can't be
+	/// copied from a linear tiled image).
+	fn image_write_depth(&mut self, image: &Self::Image, pixels: &[u8],
+		position: (u16, u16), size: (u16, u16)) -> Result<(), Error>;
+	/// Copy a rectangle of a linear tiled image into the same rectangle of
+	/// level 0 of an optimal tiled image.
+	fn image_copy_region(&mut self, src: &Self::Image, dst: &Self::Image,
+		x: u16, y: u16, width: u16, height: u16);
+	/// Copy a linear tiled image into mip level `level` of an optimal tiled
+	/// image.
+	fn image_copy(&mut self, src: &Self::Image, dst: &Self::Image,
+		width: u16, height: u16, level: u32);
+	/// Whether optimal tiled images of `format` can be blitted with linear
+	/// filtering, to make mip levels.
+	fn linear_blit(&self, format: VkFormat) -> bool;
+	/// Whether images of `format` can be made with `tiling` and `usage`.
+	fn format_supported(&self, format: VkFormat, tiling: VkImageTiling,
+		usage: VkImageUsage) -> bool;
+	/// Fill in mip levels 1 to `levels - 1` of an optimal tiled image by
+	/// blitting each from the one before, starting with level 0.
+	fn image_mipmaps(&mut self, image: &Self::Image, width: u16,
+		height: u16, levels: u32);
+	/// Copy a rendered image into a mappable image, and wait for it to
+	/// finish.
+	fn image_read_back(&mut self, src: Self::PresentImage, dst: &Self::Image,
+		width: u16, height: u16);
+	/// Read RGBA pixels out of a mappable image.
+	fn image_read(&mut self, image: &Self::Image, rgba: &mut [u8],
+		width: usize, height: usize, pitch: usize) -> Result<(), Error>;
+	/// Free an image.  It must not be in use by the GPU.
+	fn image_drop(&mut self, image: Self::Image);
+
+	fn buffer(&mut self, data: &[f32]) -> Result<Self::Buffer, Error>;
+	/// Free a buffer.  It must not be in use by the GPU.
+	fn buffer_drop(&mut self, buffer: Self::Buffer);
+	fn vertex_buffer(&self, buffer: &Self::Buffer) -> Self::VertexBuffer;
+
+	/// Get the vendor ID, device ID and pipeline cache UUID, which the
+	/// header of a saved pipeline cache must match.
+	fn pipeline_cache_id(&self) -> (u32, u32, [u8; 16]);
+	/// Create a pipeline cache, seeded with `data` (which may be empty).
+	fn pipeline_cache(&mut self, data: &[u8]) -> Self::PipelineCache;
+	/// Get the contents of a pipeline cache, to save.
+	fn pipeline_cache_data(&mut self, cache: &Self::PipelineCache)
+		-> Vec<u8>;
+	fn pipeline_cache_drop(&mut self, cache: Self::PipelineCache);
+
+	/// Get the most samples anisotropic filtering may take, 1 if it isn't
+	/// supported.
+	fn max_anisotropy(&self) -> u8;
+	/// Create a sampler.  Its anisotropy must be supported.
+	fn sampler(&mut self, desc: &SamplerDesc) -> Self::Sampler;
+	/// Free a sampler.  It must not be in use by any sprite.
+	fn sampler_drop(&mut self, sampler: Self::Sampler);
+
+	fn shader(&mut self, spirv: &[u8]) -> Self::Shader;
+	/// Create a style for `render_pass`, which has `samples` samples per
+	/// pixel.
+	fn style(&mut self, render_pass: Self::RenderPass,
+		cache: &Self::PipelineCache, vert: &Self::Shader,
+		frag: &Self::Shader, textures: u32, vertex_buffers: u32,
+		alpha: bool, samples: VkSampleCount) -> Self::Style;
+	fn sprite<T: Clone>(&mut self, style: &Self::Style, uniform: T,
+		camera: &Self::CameraMemory, effect: Option<&Self::EffectMemory>,
+		texture: Option<(Self::Image, Self::Sampler)>,
+		has_texture: bool) -> Self::Sprite;
+	/// Overwrite the start of a sprite's uniform memory.
+	fn sprite_write<T: Clone>(&mut self, sprite: &Self::Sprite,
+		uniform: &T) -> Result<(), Error>;
+	/// Make a sprite draw with `style`, which must be made from the same
+	/// shaders as its old style.
+	fn sprite_restyle(&mut self, sprite: &mut Self::Sprite,
+		style: &Self::Style);
+	/// Free a sprite's uniform memory and descriptor set.  It must not be
+	/// in use by the GPU.
+	fn sprite_drop(&mut self, sprite: Self::Sprite);
+
+	fn camera(&mut self, rgba: (f32, f32, f32, f32), fog: (f32, f32))
+		-> (Self::CameraMemory, Self::EffectMemory);
+	fn camera_write(&mut self, memory: &mut Self::CameraMemory,
+		camera: TransformUniform);
+	fn effect_write(&mut self, memory: &mut Self::EffectMemory,
+		effect: FogUniform);
+
+	fn semaphore(&mut self) -> Self::Semaphore;
+	fn semaphore_drop(&mut self, semaphore: Self::Semaphore);
+	fn fence(&mut self) -> Self::Fence;
+	fn fence_wait(&mut self, fence: &Self::Fence) -> Result<(), Error>;
+	fn fence_reset(&mut self, fence: &Self::Fence);
+	fn fence_drop(&mut self, fence: Self::Fence);
+	fn command_buffer(&mut self) -> Self::CommandBuffer;
+	fn command_buffer_drop(&mut self, command_buffer: Self::CommandBuffer);
+
+	/// Get the index of the next swapchain image, signaling `acquired` when
+	/// it's ready to be rendered into, and whether the swapchain is
+	/// suboptimal (still usable, but should be made again).
+	fn next_image(&mut self, acquired: &Self::Semaphore)
+		-> Result<(u32, bool), Error>;
+	/// Start recording into `command_buffer`.  Everything up to `draw_end()`
+	/// is recorded into it.
+	fn draw_begin(&mut self, command_buffer: &Self::CommandBuffer,
+		render_pass: Self::RenderPass, image: Self::PresentImage,
+		frame_buffer: Self::Framebuffer);
+	fn bind_vb(&mut self, buffers: &[Self::VertexBuffer]);
+	fn bind_sprite(&mut self, sprite: &Self::Sprite);
+	fn draw(&mut self, vertex_count: u32, first_vertex: u32);
+	/// End the render pass, transitioning `image` for presenting if it's
+	/// `Some`.
+	fn draw_end(&mut self, image: Option<Self::PresentImage>);
+	/// Submit `command_buffer` without waiting for it to finish.  It waits
+	/// on `wait`, and signals `signal` and `fence` when done.
+	fn queue_submit(&mut self, command_buffer: &Self::CommandBuffer,
+		wait: Option<&Self::Semaphore>, signal: Option<&Self::Semaphore>,
+		fence: &Self::Fence) -> Result<(), Error>;
+	/// Present a swapchain image once `wait` is signaled.
+	fn queue_present(&mut self, image_index: u32, wait: &Self::Semaphore)
+		-> Result<(), Error>;
+	/// Make a new logical device to replace a lost one.  Everything made
+	/// on the lost device has to be made again, but can still be freed.
+	fn device_reset(&mut self) -> Result<(), Error>;
+
+	/// Name objects, so validation messages say which one they're about.
+	fn name_buffer(&mut self, buffer: &Self::Buffer, name: &str);
+	fn name_image(&mut self, image: &Self::Image, name: &str);
+	fn name_style(&mut self, style: &Self::Style, name: &str);
+}
+
+/// Turn a failed `VkResult` into an `Error`.
+fn error(result: VkResult) -> Error {
+	match result {
+		VkResult::ErrorOutOfHostMemory
+			| VkResult::ErrorOutOfDeviceMemory
+			=> Error::OutOfDeviceMemory,
+		VkResult::ErrorMemoryMapFailed => Error::MapFailed,
+		// Either way the swapchain has to be made again (acquiring
+		// doesn't fail when it's suboptimal, but presenting may).
+		VkResult::ErrorSurfaceLostKhr
+			| VkResult::ErrorOutOfDateKhr
+			| VkResult::SuboptimalKhr => Error::SurfaceLost,
+		VkResult::ErrorFormatNotSupported => Error::UnsupportedFormat,
+		VkResult::ErrorDeviceLost => Error::DeviceLost,
+		_ => Error::Vulkan(result),
+	}
+}
+
+impl Device for Gpu {
+	type Image = asi_vulkan::Image;
+	type Buffer = asi_vulkan::Buffer;
+	type VertexBuffer = VkBuffer;
+	type Shader = asi_vulkan::ShaderModule;
+	type Style = asi_vulkan::Style;
+	type Sprite = asi_vulkan::Sprite;
+	type CameraMemory = asi_vulkan::Memory<TransformUniform>;
+	type EffectMemory = asi_vulkan::Memory<FogUniform>;
+	type RenderPass = VkRenderPass;
+	type Framebuffer = VkFramebuffer;
+	type PresentImage = VkImage;
+	type ImageView = VkImageView;
+	type Fence = VkFence;
+	type Semaphore = VkSemaphore;
+	type CommandBuffer = VkCommandBuffer;
+	type PipelineCache = VkPipelineCache;
+	type Sampler = VkSampler;
+
+	fn sampled(&self) -> bool {
+		Gpu::sampled(self)
+	}
+
+	fn color(&mut self, rgb: Vec3) {
+		Gpu::color(self, rgb);
+	}
+
+	fn present_modes(&mut self) -> Vec<PresentMode> {
+		let modes = unsafe { asi_vulkan::get_present_modes(self) };
+
+		modes.iter().filter_map(|mode| match *mode {
+			VkPresentModeKHR::Fifo => Some(PresentMode::Fifo),
+			VkPresentModeKHR::Mailbox => Some(PresentMode::Mailbox),
+			VkPresentModeKHR::Immediate => Some(PresentMode::Immediate),
+			_ => None,
+		}).collect()
+	}
+
+	fn image_counts(&mut self) -> (u32, u32) {
+		unsafe { asi_vulkan::get_image_counts(self) }
+	}
+
+	fn present_format(&self) -> VkFormat {
+		Gpu::format(self)
+	}
+
+	fn sample_counts(&self) -> u32 {
+		Gpu::sample_counts(self)
+	}
+
+	fn swapchain_new(&mut self, present_mode: PresentMode,
+		image_count: &mut u32, samples: VkSampleCount,
+		present_images: &mut [VkImage; MAX_IMAGES],
+		present_image_views: &mut [VkImageView; MAX_IMAGES],
+		frame_buffers: &mut [VkFramebuffer; MAX_IMAGES])
+		-> (asi_vulkan::Image, Option<asi_vulkan::Image>, VkRenderPass)
+	{
+		unsafe {
+			let submit_fence;
+			let depth_image;
+			let ms_image;
+			let render_pass;
+
+			// Link swapchain to vulkan instance.
+			asi_vulkan::create_swapchain(
+				self,
+				match present_mode {
+					PresentMode::Fifo =>
+						VkPresentModeKHR::Fifo,
+					PresentMode::Mailbox =>
+						VkPresentModeKHR::Mailbox,
+					PresentMode::Immediate =>
+						VkPresentModeKHR::Immediate,
+				},
+				image_count,
+				&mut present_images[0]
+			);
+
+			// Link Image Views for each framebuffer
+			submit_fence = asi_vulkan::create_image_view(
+				self,
+				*image_count,
+				present_images,
+				present_image_views,
+			);
+
+			// Link Depth Buffer to swapchain
+			depth_image = asi_vulkan::create_depth_buffer(
+				self,
+				&submit_fence,
+				samples,
+			);
+
+			// Create multisampling buffer, unless there's only one
+			// sample per pixel.
+			ms_image = if samples == VkSampleCount::Sc1 {
+				None
+			} else {
+				Some(asi_vulkan::create_ms_buffer(
+					self,
+					samples,
+				))
+			};
+
+			// Link Render Pass to swapchain
+			render_pass = asi_vulkan::create_render_pass(
+				self,
+				samples,
+			);
+
+			// Link Framebuffers to swapchain
+			asi_vulkan::create_framebuffers(
+				self,
+				*image_count,
+				render_pass,
+				present_image_views,
+				ms_image.as_ref(),
+				&depth_image,
+				frame_buffers,
+			);
+
+			(depth_image, ms_image, render_pass)
+		}
+	}
+
+	fn swapchain_drop(&mut self, frame_buffers: &[VkFramebuffer; MAX_IMAGES],
+		present_image_views: &[VkImageView; MAX_IMAGES],
+		render_pass: VkRenderPass, image_count: u32)
+	{
+		unsafe {
+			asi_vulkan::destroy_swapchain(
+				self,
+				frame_buffers,
+				present_image_views,
+				render_pass,
+				image_count,
+			);
+		}
+	}
+
+	fn offscreen_new(&mut self, width: u16, height: u16,
+		samples: VkSampleCount,
+		present_images: &mut [VkImage; MAX_IMAGES],
+		present_image_views: &mut [VkImageView; MAX_IMAGES],
+		frame_buffers: &mut [VkFramebuffer; MAX_IMAGES])
+		-> (asi_vulkan::Image, asi_vulkan::Image,
+			Option<asi_vulkan::Image>, VkRenderPass)
+	{
+		unsafe {
+			let submit_fence;
+			let color_image;
+			let depth_image;
+			let ms_image;
+			let render_pass;
+
+			// Create an image to render into, instead of a
+			// swapchain.
+			color_image = asi_vulkan::create_offscreen(
+				self,
+				width as u32,
+				height as u32,
+			);
+			present_images[0] = color_image.image();
+
+			// Link Image View for the framebuffer
+			submit_fence = asi_vulkan::create_image_view(
+				self,
+				1,
+				present_images,
+				present_image_views,
+			);
+
+			// Link Depth Buffer to offscreen image
+			depth_image = asi_vulkan::create_depth_buffer(
+				self,
+				&submit_fence,
+				samples,
+			);
+
+			// Create multisampling buffer, unless there's only one
+			// sample per pixel.
+			ms_image = if samples == VkSampleCount::Sc1 {
+				None
+			} else {
+				Some(asi_vulkan::create_ms_buffer(
+					self,
+					samples,
+				))
+			};
+
+			// Link Render Pass to offscreen image
+			render_pass = asi_vulkan::create_render_pass(
+				self,
+				samples,
+			);
+
+			// Link Framebuffer to offscreen image
+			asi_vulkan::create_framebuffers(
+				self,
+				1,
+				render_pass,
+				present_image_views,
+				ms_image.as_ref(),
+				&depth_image,
+				frame_buffers,
+			);
+
+			(color_image, depth_image, ms_image, render_pass)
+		}
+	}
+
+	fn offscreen_drop(&mut self, frame_buffers: &[VkFramebuffer; MAX_IMAGES],
+		present_image_views: &[VkImageView; MAX_IMAGES],
+		render_pass: VkRenderPass)
+	{
+		unsafe {
+			asi_vulkan::destroy_offscreen(
+				self,
+				frame_buffers,
+				present_image_views,
+				render_pass,
+			);
+		}
+	}
+
+	fn image(&mut self, width: u32, height: u32, levels: u32,
+		format: VkFormat, tiling: VkImageTiling, usage: VkImageUsage,
+		layout: VkImageLayout, properties: u32, samples: VkSampleCount)
+		-> Result<asi_vulkan::Image, Error>
+	{
+		asi_vulkan::Image::new(self, width, height, levels, format,
+			tiling, usage, layout, properties, samples)
+			.map_err(error)
+	}
+
+	fn image_pitch(&mut self, image: &asi_vulkan::Image) -> u32 {
+		let layout = unsafe { asi_vulkan::subres_layout(self, image) };
+
+		layout.row_pitch as u32
+	}
+
+	fn image_write(&mut self, image: &asi_vulkan::Image, pixels: &[u8],
+		width: usize, height: usize, pitch: usize, bpp: usize)
+		-> Result<(), Error>
+	{
+		ffi::copy_memory_pitched(self, image.memory(), pixels, (0, 0),
+			(width, height), (pitch, bpp))
+	}
+
+	fn image_write_region(&mut self, image: &asi_vulkan::Image,
+		pixels: &[u8], (x, y): (u16, u16), (width, height): (u16, u16),
+		pitch: usize, bpp: usize) -> Result<(), Error>
+	{
+		ffi::copy_memory_pitched(self, image.memory(), pixels,
+			(x as usize, y as usize),
+			(width as usize, height as usize), (pitch, bpp))
+	}
+
+	fn image_write_depth(&mut self, image: &asi_vulkan::Image,
+		pixels: &[u8], (x, y): (u16, u16), (width, height): (u16, u16))
+		-> Result<(), Error>
+	{
+		unsafe {
+			asi_vulkan::copy_buffer_to_image(self, pixels, image, x,
+				y, width, height, VkImageAspectFlags::Depth)
+		}.map_err(error)
+	}
+
+	fn image_copy_region(&mut self, src: &asi_vulkan::Image,
+		dst: &asi_vulkan::Image, x: u16, y: u16, width: u16,
+		height: u16)
+	{
+		unsafe {
+			asi_vulkan::copy_image_region(self, src, dst, x, y,
+				width, height);
+		}
+	}
+
+	fn image_copy(&mut self, src: &asi_vulkan::Image,
+		dst: &asi_vulkan::Image, width: u16, height: u16, level: u32)
+	{
+		unsafe {
+			asi_vulkan::copy_image(self, src, dst, width, height,
+				level);
+		}
+	}
+
+	fn linear_blit(&self, format: VkFormat) -> bool {
+		Gpu::linear_blit(self, format)
+	}
+
+	fn format_supported(&self, format: VkFormat, tiling: VkImageTiling,
+		usage: VkImageUsage) -> bool
+	{
+		asi_vulkan::format_supported(self, format, tiling, usage)
+	}
+
+	fn image_mipmaps(&mut self, image: &asi_vulkan::Image, width: u16,
+		height: u16, levels: u32)
+	{
+		unsafe {
+			asi_vulkan::generate_mipmaps(self, image, width, height,
+				levels);
+		}
+	}
+
+	fn image_read_back(&mut self, src: VkImage, dst: &asi_vulkan::Image,
+		width: u16, height: u16)
+	{
+		unsafe {
+			asi_vulkan::copy_present_image(self, src, dst, width,
+				height);
+		}
+	}
+
+	fn image_read(&mut self, image: &asi_vulkan::Image, rgba: &mut [u8],
+		width: usize, height: usize, pitch: usize) -> Result<(), Error>
+	{
+		ffi::read_memory_pitched(self, image.memory(), rgba, width,
+			height, pitch)
+	}
+
+	fn image_drop(&mut self, image: asi_vulkan::Image) {
+		// Freed by asi_vulkan when the last reference is dropped.
+		drop(image);
+	}
+
+	fn buffer(&mut self, data: &[f32])
+		-> Result<asi_vulkan::Buffer, Error>
+	{
+		unsafe { asi_vulkan::new_buffer(self, data) }.map_err(error)
+	}
+
+	fn buffer_drop(&mut self, buffer: asi_vulkan::Buffer) {
+		// Freed by asi_vulkan when the last reference is dropped.
+		drop(buffer);
+	}
+
+	fn vertex_buffer(&self, buffer: &asi_vulkan::Buffer) -> VkBuffer {
+		buffer.buffer()
+	}
+
+	fn pipeline_cache_id(&self) -> (u32, u32, [u8; 16]) {
+		Gpu::pipeline_cache_id(self)
+	}
+
+	fn pipeline_cache(&mut self, data: &[u8]) -> VkPipelineCache {
+		unsafe { asi_vulkan::pipeline_cache::new(self, data) }
+	}
+
+	fn pipeline_cache_data(&mut self, cache: &VkPipelineCache) -> Vec<u8> {
+		unsafe { asi_vulkan::pipeline_cache::data(self, *cache) }
+	}
+
+	fn pipeline_cache_drop(&mut self, cache: VkPipelineCache) {
+		unsafe { asi_vulkan::pipeline_cache::destroy(self, cache) }
+	}
+
+	fn max_anisotropy(&self) -> u8 {
+		Gpu::max_anisotropy(self).max(1.0).min(16.0) as u8
+	}
+
+	fn sampler(&mut self, desc: &SamplerDesc) -> VkSampler {
+		let filter = |filter| match filter {
+			Filter::Nearest => VkFilter::Nearest,
+			Filter::Linear => VkFilter::Linear,
+		};
+		let address = |mode| match mode {
+			AddressMode::Repeat => VkSamplerAddressMode::Repeat,
+			AddressMode::MirroredRepeat => {
+				VkSamplerAddressMode::MirroredRepeat
+			}
+			AddressMode::ClampToEdge => {
+				VkSamplerAddressMode::ClampToEdge
+			}
+			AddressMode::ClampToBorder => {
+				VkSamplerAddressMode::ClampToBorder
+			}
+		};
+
+		let mip_filter = match desc.mip_filter {
+			Filter::Nearest => VkSamplerMipmapMode::Nearest,
+			Filter::Linear => VkSamplerMipmapMode::Linear,
+		};
+		let border_color = match desc.border_color {
+			BorderColor::TransparentBlack => {
+				VkBorderColor::FloatTransparentBlack
+			}
+			BorderColor::OpaqueBlack => {
+				VkBorderColor::FloatOpaqueBlack
+			}
+			BorderColor::OpaqueWhite => {
+				VkBorderColor::FloatOpaqueWhite
+			}
+		};
+
+		unsafe {
+			asi_vulkan::create_sampler(self,
+				filter(desc.min_filter),
+				filter(desc.mag_filter),
+				mip_filter,
+				address(desc.address_u),
+				address(desc.address_v),
+				desc.anisotropy as f32,
+				border_color)
+		}
+	}
+
+	fn sampler_drop(&mut self, sampler: VkSampler) {
+		unsafe { asi_vulkan::destroy_sampler(self, sampler) }
+	}
+
+	fn shader(&mut self, spirv: &[u8]) -> asi_vulkan::ShaderModule {
+		asi_vulkan::ShaderModule::new(self, spirv)
+	}
+
+	fn style(&mut self, render_pass: VkRenderPass, cache: &VkPipelineCache,
+		vert: &asi_vulkan::ShaderModule,
+		frag: &asi_vulkan::ShaderModule, textures: u32,
+		vertex_buffers: u32, alpha: bool, samples: VkSampleCount)
+		-> asi_vulkan::Style
+	{
+		asi_vulkan::Style::new(self, render_pass, vert, frag, textures,
+			vertex_buffers, alpha, samples, *cache)
+	}
+
+	fn sprite<T: Clone>(&mut self, style: &asi_vulkan::Style, uniform: T,
+		camera: &asi_vulkan::Memory<TransformUniform>,
+		effect: Option<&asi_vulkan::Memory<FogUniform>>,
+		texture: Option<(asi_vulkan::Image, VkSampler)>,
+		has_texture: bool) -> asi_vulkan::Sprite
+	{
+		unsafe {
+			asi_vulkan::Sprite::new(self, style, uniform, camera,
+				effect, texture, has_texture)
+		}
+	}
+
+	fn sprite_write<T: Clone>(&mut self, sprite: &asi_vulkan::Sprite,
+		uniform: &T) -> Result<(), Error>
+	{
+		ffi::copy_memory(self, sprite.uniform_memory.memory(), uniform)
+	}
+
+	fn sprite_restyle(&mut self, sprite: &mut asi_vulkan::Sprite,
+		style: &asi_vulkan::Style)
+	{
+		// The descriptor set layout is the same, so only the pipeline
+		// changes.
+		sprite.pipeline = style.pipeline;
+		sprite.pipeline_layout = style.pipeline_layout;
+	}
+
+	fn sprite_drop(&mut self, mut sprite: asi_vulkan::Sprite) {
+		unsafe { asi_vulkan::destroy_uniforms(self, &mut sprite) }
+	}
+
+	fn camera(&mut self, rgba: (f32, f32, f32, f32), fog: (f32, f32))
+		-> (asi_vulkan::Memory<TransformUniform>,
+			asi_vulkan::Memory<FogUniform>)
+	{
+		unsafe { asi_vulkan::vw_camera_new(self, rgba, fog) }
+	}
+
+	fn camera_write(&mut self,
+		memory: &mut asi_vulkan::Memory<TransformUniform>,
+		camera: TransformUniform)
+	{
+		memory.data = camera;
+		memory.update(self);
+	}
+
+	fn effect_write(&mut self, memory: &mut asi_vulkan::Memory<FogUniform>,
+		effect: FogUniform)
+	{
+		memory.data = effect;
+		memory.update(self);
+	}
+
+	fn semaphore(&mut self) -> VkSemaphore {
+		unsafe { asi_vulkan::new_semaphore(self) }
+	}
+
+	fn semaphore_drop(&mut self, semaphore: VkSemaphore) {
+		unsafe { asi_vulkan::drop_semaphore(self, semaphore) }
+	}
+
+	fn fence(&mut self) -> VkFence {
+		unsafe { asi_vulkan::fence::new(self) }
+	}
+
+	fn fence_wait(&mut self, fence: &VkFence) -> Result<(), Error> {
+		unsafe { asi_vulkan::fence::wait(self, *fence) }.map_err(error)
+	}
+
+	fn fence_reset(&mut self, fence: &VkFence) {
+		unsafe { asi_vulkan::fence::reset(self, *fence) }
+	}
+
+	fn fence_drop(&mut self, fence: VkFence) {
+		unsafe { asi_vulkan::fence::drop(self, fence) }
+	}
+
+	fn command_buffer(&mut self) -> VkCommandBuffer {
+		unsafe { asi_vulkan::command_buffer::new(self) }
+	}
+
+	fn command_buffer_drop(&mut self, command_buffer: VkCommandBuffer) {
+		unsafe { asi_vulkan::command_buffer::drop(self, command_buffer) }
+	}
+
+	fn next_image(&mut self, acquired: &VkSemaphore)
+		-> Result<(u32, bool), Error>
+	{
+		unsafe { asi_vulkan::get_next_image(self, *acquired) }
+			.map_err(error)
+	}
+
+	fn draw_begin(&mut self, command_buffer: &VkCommandBuffer,
+		render_pass: VkRenderPass, image: VkImage,
+		frame_buffer: VkFramebuffer)
+	{
+		unsafe {
+			asi_vulkan::draw_begin(self, *command_buffer,
+				render_pass, image, frame_buffer);
+		}
+	}
+
+	fn bind_vb(&mut self, buffers: &[VkBuffer]) {
+		unsafe { asi_vulkan::cmd_bind_vb(self, buffers) }
+	}
+
+	fn bind_sprite(&mut self, sprite: &asi_vulkan::Sprite) {
+		unsafe {
+			asi_vulkan::cmd_bind_pipeline(self, sprite.pipeline);
+			asi_vulkan::cmd_bind_descsets(self,
+				sprite.pipeline_layout,
+				sprite.handles().0/*desc_set*/);
+		}
+	}
+
+	fn draw(&mut self, vertex_count: u32, first_vertex: u32) {
+		unsafe {
+			asi_vulkan::cmd_draw(self, vertex_count, 1,
+				first_vertex, 0);
+		}
+	}
+
+	fn draw_end(&mut self, image: Option<VkImage>) {
+		unsafe {
+			asi_vulkan::end_render_pass(self);
+			if let Some(image) = image {
+				asi_vulkan::pipeline_barrier(self, image);
+			}
+			asi_vulkan::end_cmdbuff(self);
+		}
+	}
+
+	fn queue_submit(&mut self, command_buffer: &VkCommandBuffer,
+		wait: Option<&VkSemaphore>, signal: Option<&VkSemaphore>,
+		fence: &VkFence) -> Result<(), Error>
+	{
+		unsafe {
+			asi_vulkan::queue_submit(self, *command_buffer, *fence,
+				VkPipelineStage::ColorAttachmentOutput,
+				wait.cloned(), signal.cloned())
+		}.map_err(error)
+	}
+
+	fn queue_present(&mut self, image_index: u32, wait: &VkSemaphore)
+		-> Result<(), Error>
+	{
+		unsafe { asi_vulkan::queue_present(self, image_index, *wait) }
+			.map_err(error)
+	}
+
+	fn device_reset(&mut self) -> Result<(), Error> {
+		unsafe { asi_vulkan::reset_device(self) }.map_err(error)
+	}
+
+	fn name_buffer(&mut self, buffer: &asi_vulkan::Buffer, name: &str) {
+		unsafe {
+			asi_vulkan::name_object(self, VkObjectType::Buffer,
+				buffer.buffer(), name)
+		}
+	}
+
+	fn name_image(&mut self, image: &asi_vulkan::Image, name: &str) {
+		unsafe {
+			asi_vulkan::name_object(self, VkObjectType::Image,
+				image.image(), name)
+		}
+	}
+
+	fn name_style(&mut self, style: &asi_vulkan::Style, name: &str) {
+		unsafe {
+			asi_vulkan::name_object(self, VkObjectType::Pipeline,
+				style.pipeline, name)
+		}
+	}
+}
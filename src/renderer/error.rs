@@ -0,0 +1,89 @@
+// Copyright Jeron A. Lau 2018.
+// Dual-licensed under either the MIT License or the Boost Software License,
+// Version 1.0.  (See accompanying file LICENSE_1_0.txt or copy at
+// https://www.boost.org/LICENSE_1_0.txt)
+
+use std::{ error, fmt };
+
+use asi_vulkan::types::VkResult;
+
+use super::HandleError;
+
+/// Why a `Renderer` operation failed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+	/// Vulkan couldn't be started, for example because there's no driver
+	/// or GPU.
+	Init(String),
+	/// There wasn't enough GPU (or host) memory.
+	OutOfDeviceMemory,
+	/// GPU memory couldn't be mapped for the CPU to use.
+	MapFailed,
+	/// The window's surface was lost, or the swapchain no longer matches
+	/// it.
+	SurfaceLost,
+	/// The GPU was lost: reset, removed or crashed.
+	DeviceLost,
+	/// A handle couldn't be used.
+	InvalidHandle(HandleError),
+	/// There are as many objects of a kind as there can be handles for
+	/// (2^20).
+	OutOfHandles,
+	/// Texcoords or a gradient don't have one entry per vertex of the
+	/// model, or a shape doesn't have the vertex buffers its style needs.
+	MismatchedVertexCount,
+	/// A shape doesn't have the textures its style needs.
+	MismatchedTextureCount,
+	/// A region isn't inside its texture, or the pixels don't fill it.
+	InvalidRegion,
+	/// The GPU doesn't support a format that's needed.
+	UnsupportedFormat,
+	/// A custom style's shaders don't match what the renderer gives them.
+	InvalidShader(String),
+	/// Vulkan failed in a way that isn't covered above.
+	Vulkan(VkResult),
+}
+
+impl From<HandleError> for Error {
+	fn from(error: HandleError) -> Error {
+		Error::InvalidHandle(error)
+	}
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Error::Init(ref why) => {
+				write!(f, "couldn't start Vulkan: {}", why)
+			}
+			Error::OutOfDeviceMemory => {
+				f.write_str("out of memory")
+			}
+			Error::MapFailed => {
+				f.write_str("couldn't map GPU memory")
+			}
+			Error::SurfaceLost => f.write_str("surface lost"),
+			Error::DeviceLost => f.write_str("GPU lost"),
+			Error::InvalidHandle(ref e) => e.fmt(f),
+			Error::OutOfHandles => f.write_str("out of handles"),
+			Error::MismatchedVertexCount => {
+				f.write_str("vertex counts don't match")
+			}
+			Error::MismatchedTextureCount => {
+				f.write_str("texture counts don't match")
+			}
+			Error::InvalidRegion => {
+				f.write_str("region doesn't fit the texture")
+			}
+			Error::UnsupportedFormat => {
+				f.write_str("format not supported")
+			}
+			Error::InvalidShader(ref why) => f.write_str(why),
+			Error::Vulkan(result) => {
+				write!(f, "Vulkan failed: {:?}", result)
+			}
+		}
+	}
+}
+
+impl error::Error for Error { }
@@ -9,41 +9,81 @@ use asi_vulkan;
 use asi_vulkan::types::*;
 use asi_vulkan::Gpu;
 
+use super::Error;
+
 pub fn copy_memory<T>(connection: &Gpu, vk_memory: VkDeviceMemory,
-	data: &T) where T: Clone
+	data: &T) -> Result<(), Error> where T: Clone
 {
 	let mapped : *mut T = unsafe {
 		asi_vulkan::map_memory(connection, vk_memory, !0)
 	};
 
 	if mapped.is_null() {
-		panic!("Couldn't Map Buffer Memory?  Unknown cause.");
+		return Err(Error::MapFailed);
 	}
 
 	unsafe {
 		*mapped = data.clone();
 		asi_vulkan::unmap_memory(connection, vk_memory);
 	}
+
+	Ok(())
 }
 
+/// Copy `width` by `height` pixels of `bpp` bytes each into memory with rows
+/// `pitch` bytes apart, with their top left corner at `(x, y)`.
 pub fn copy_memory_pitched(connection: &Gpu, vk_memory: VkDeviceMemory,
-	data: &[u8], width: usize, height: usize, pitch: usize)
+	data: &[u8], (x, y): (usize, usize), (width, height): (usize, usize),
+	(pitch, bpp): (usize, usize)) -> Result<(), Error>
 {
 	let mapped : *mut u8 = unsafe {
 		asi_vulkan::map_memory(connection, vk_memory, !0)
 	};
 
 	if mapped.is_null() {
-		panic!("Couldn't Map Buffer Memory?  Unknown cause.");
+		return Err(Error::MapFailed);
+	}
+
+	for i in 0..height {
+		for j in 0..width {
+			for k in 0..bpp {
+				unsafe {
+					let from = (i * width + j) * bpp + k;
+
+					*(mapped.offset(((y + i) * pitch
+						+ (x + j) * bpp + k) as isize))
+						= data[from];
+				}
+			}
+		}
+	}
+
+	unsafe {
+		asi_vulkan::unmap_memory(connection, vk_memory);
+	}
+
+	Ok(())
+}
+
+pub fn read_memory_pitched(connection: &Gpu, vk_memory: VkDeviceMemory,
+	data: &mut [u8], width: usize, height: usize, pitch: usize)
+	-> Result<(), Error>
+{
+	let mapped : *const u8 = unsafe {
+		asi_vulkan::map_memory(connection, vk_memory, !0)
+	};
+
+	if mapped.is_null() {
+		return Err(Error::MapFailed);
 	}
 
 	for i in 0..height {
 		for j in 0..width {
 			for k in 0..4 {
 				unsafe {
-					*(mapped.offset((i * pitch + j * 4 + k)
-							as isize))
-						= data[(i * width + j) * 4 + k];
+					data[(i * width + j) * 4 + k] =
+						*(mapped.offset((i * pitch
+							+ j * 4 + k) as isize));
 				}
 			}
 		}
@@ -52,4 +92,6 @@ pub fn copy_memory_pitched(connection: &Gpu, vk_memory: VkDeviceMemory,
 	unsafe {
 		asi_vulkan::unmap_memory(connection, vk_memory);
 	}
+
+	Ok(())
 }
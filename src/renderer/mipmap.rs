@@ -0,0 +1,84 @@
+// Copyright Jeron A. Lau 2018.
+// Dual-licensed under either the MIT License or the Boost Software License,
+// Version 1.0.  (See accompanying file LICENSE_1_0.txt or copy at
+// https://www.boost.org/LICENSE_1_0.txt)
+
+//! Making mip levels on the CPU, for formats the GPU can't blit.
+
+/// Get the number of mip levels in a full chain for a `width` by `height`
+/// image, down to 1x1.
+pub fn levels(width: u16, height: u16) -> u32 {
+	32 - (width.max(height).max(1) as u32).leading_zeros()
+}
+
+/// Get the size of the next mip level.
+pub fn next_size(width: u16, height: u16) -> (u16, u16) {
+	((width / 2).max(1), (height / 2).max(1))
+}
+
+/// Make the next mip level of 8-bit `pixels` with `channels` channels by
+/// averaging each 2x2 box.  On odd sizes the last row or column is used twice.
+/// If `srgb`, the color channels (all but alpha) are averaged as linear values.
+pub fn half(pixels: &[u8], width: u16, height: u16, channels: usize,
+	srgb: bool) -> Vec<u8>
+{
+	let (w, h) = (width as usize, height as usize);
+	let (next_w, next_h) = next_size(width, height);
+	let (next_w, next_h) = (next_w as usize, next_h as usize);
+	let mut next = Vec::with_capacity(next_w * next_h * channels);
+
+	for y in 0..next_h {
+		let y0 = (y * 2).min(h - 1);
+		let y1 = (y * 2 + 1).min(h - 1);
+
+		for x in 0..next_w {
+			let x0 = (x * 2).min(w - 1);
+			let x1 = (x * 2 + 1).min(w - 1);
+
+			for c in 0..channels {
+				let at = |i: usize| pixels[i * channels + c];
+				let boxed = [at(y0 * w + x0), at(y0 * w + x1),
+					at(y1 * w + x0), at(y1 * w + x1)];
+
+				next.push(average(boxed, srgb && c != 3));
+			}
+		}
+	}
+
+	next
+}
+
+/// Average a 2x2 box of values, as linear values if they're `srgb`.
+fn average(boxed: [u8; 4], srgb: bool) -> u8 {
+	if srgb {
+		let sum = boxed.iter().fold(0.0, |sum, v| sum + to_linear(*v));
+
+		to_srgb(sum / 4.0)
+	} else {
+		let sum = boxed.iter().fold(0, |sum, v| sum + *v as u32);
+
+		((sum + 2) / 4) as u8
+	}
+}
+
+/// Decode an sRGB value.
+fn to_linear(srgb: u8) -> f32 {
+	let c = srgb as f32 / 255.0;
+
+	if c <= 0.04045 {
+		c / 12.92
+	} else {
+		((c + 0.055) / 1.055).powf(2.4)
+	}
+}
+
+/// Encode a linear value as sRGB.
+fn to_srgb(linear: f32) -> u8 {
+	let c = if linear <= 0.0031308 {
+		linear * 12.92
+	} else {
+		1.055 * linear.powf(1.0 / 2.4) - 0.055
+	};
+
+	(c * 255.0 + 0.5) as u8
+}
@@ -6,96 +6,270 @@
 use afi::Video;
 
 use std::{ mem };
+use std::collections::HashMap;
+use std::collections::HashSet;
 
 use adi_gpu_base::*;
 
+mod cache;
+mod device;
+mod error;
 mod ffi;
+mod mipmap;
+mod recorder;
+mod slots;
+mod spirv;
+mod uniforms;
+
+pub use self::device::Device;
+pub use self::error::Error;
+pub use self::recorder::{ Call, Recorder };
+pub use self::slots::HandleError;
+
+use self::slots::{ Kind, Slots };
+use self::uniforms::*;
 
-use asi_vulkan;
 use asi_vulkan::types::*;
-use asi_vulkan::Image;
-use asi_vulkan::Style;
-use asi_vulkan::Buffer;
 
 // TODO
 use asi_vulkan::TransformUniform;
 use asi_vulkan::FogUniform;
-use asi_vulkan::Sprite;
 use asi_vulkan::Gpu;
 
 use ShapeHandle;
-
-#[derive(Clone)] #[repr(C)] struct TransformFullUniform {
+use DisplayConfig;
+use DebugMode;
+use PresentMode;
+use TextureConfig;
+use ColorSpace;
+use TextureFormat;
+use SamplerDesc;
+
+/// The uniform of a custom style, with the user's part at offset 80 (where
+/// std140 puts a `vec4` or struct after `has_camera`).
+#[derive(Clone)] #[repr(C)] struct CustomUniform<U> {
 	mat4: [f32; 16],
 	hcam: u32,
+	_pad: [u32; 3],
+	data: U,
 }
 
-#[derive(Clone)] #[repr(C)] struct TransformAndFadeUniform {
-	mat4: [f32; 16],
-	fade: f32,
-	hcam: u32,
+/// The SPIR-V for a style, put in `OUT_DIR` by `build.rs`.  `bfrag` is the
+/// fragment shader for blended shapes, which discards fully transparent
+/// fragments so they don't write depth.
+struct Shaders {
+	name: &'static str,
+	vert: &'static [u8],
+	frag: &'static [u8],
+	bfrag: &'static [u8],
+	textures: u32,
+	vertex_buffers: u32,
 }
 
-#[derive(Clone)] #[repr(C)] struct TransformAndColorUniform {
-	mat4: [f32; 16],
-	vec4: [f32; 4],
-	hcam: u32,
+macro_rules! shaders {
+	($name:expr, $textures:expr, $vertex_buffers:expr) => {
+		Shaders {
+			name: $name,
+			vert: include_bytes!(concat!(env!("OUT_DIR"), "/",
+				$name, "-vert.spv")),
+			frag: include_bytes!(concat!(env!("OUT_DIR"), "/",
+				$name, "-frag.spv")),
+			bfrag: include_bytes!(concat!(env!("OUT_DIR"), "/",
+				$name, "-bfrag.spv")),
+			textures: $textures,
+			vertex_buffers: $vertex_buffers,
+		}
+	}
 }
 
-pub struct Vw {
-	connection: Gpu,
-	present_images: [VkImage; 2], // 2 for double-buffering
-	frame_buffers: [VkFramebuffer; 2], // 2 for double-buffering
-	image_count: u32, // 1 (single-buffering) or 2 (double-buffering)
-	present_image_views: [VkImageView; 2], // 2 for double-buffering
-	ms_image: Image,
-	depth_image: Image,
-	render_pass: VkRenderPass,
+const SOLID: Shaders = shaders!("solid", 0, 1);
+const TEXTURE: Shaders = shaders!("texture", 1, 2);
+const GRADIENT: Shaders = shaders!("gradient", 0, 2);
+const FADED: Shaders = shaders!("faded", 1, 2);
+const TINTED: Shaders = shaders!("tinted", 1, 2);
+const COMPLEX: Shaders = shaders!("complex", 1, 3);
+
+/// The most swapchain images there can be, more than any surface needs.
+pub const MAX_IMAGES: usize = 8;
+
+/// The number of frames the CPU may get ahead of the GPU, unless changed with
+/// `Renderer::frames_in_flight()`.
+pub const FRAMES_IN_FLIGHT: usize = 2;
+const MIN_FRAMES_IN_FLIGHT: usize = 2;
+const MAX_FRAMES_IN_FLIGHT: usize = 3;
+
+pub struct Vw<D: Device> {
+	connection: D,
+	present_images: [D::PresentImage; MAX_IMAGES], // Up to 8 images
+	frame_buffers: [D::Framebuffer; MAX_IMAGES], // Up to 8 images
+	image_count: u32, // 1 (offscreen) to 4 (quadruple-buffering)
+	present_image_views: [D::ImageView; MAX_IMAGES], // Up to 8 images
+	config: DisplayConfig, // Requested present mode, image count, etc.
+	present_mode: PresentMode, // Selected present mode
+	offscreen: Option<D::Image>, // Color image when there's no swapchain
+	samples: VkSampleCount, // Selected samples per pixel
+	ms_image: Option<D::Image>, // None with 1 sample per pixel
+	depth_image: D::Image,
+	render_pass: D::RenderPass,
+	pipeline_cache: D::PipelineCache,
 }
 
 /// A texture on the GPU.
-pub struct Texture {
-	mappable_image: Image,
-	image: Option<Image>,
+pub struct Texture<D: Device> {
+	mappable_image: Option<D::Image>, // None for depth, see `set_texture()`
+	image: Option<D::Image>,
 //	view: VkImageView,
 	pub(super) w: u16,
 	pub(super) h: u16,
 	pitch: u32,
 	staged: bool,
+	levels: u32, // Mip levels of `image`
+	config: TextureConfig,
+	rgba: Vec<u8>, // Kept to upload again if the device is lost
+}
+
+/// A shape's latest uniform, kept to make its sprites with (again if the
+/// device is lost) and to write into them.
+trait Uniform<D: Device> {
+	/// Make a sprite with this uniform.
+	fn sprite(&self, connection: &mut D, style: &D::Style,
+		camera: &D::CameraMemory, effect: &D::EffectMemory,
+		texture: Option<(D::Image, D::Sampler)>) -> D::Sprite;
+	/// Overwrite a sprite's uniform with this one.
+	fn write(&self, connection: &mut D, sprite: &D::Sprite)
+		-> Result<(), Error>;
+}
+
+impl<D: Device, T: Clone> Uniform<D> for T {
+	fn sprite(&self, connection: &mut D, style: &D::Style,
+		camera: &D::CameraMemory, effect: &D::EffectMemory,
+		texture: Option<(D::Image, D::Sampler)>) -> D::Sprite
+	{
+		let has_texture = texture.is_some();
+
+		connection.sprite(style, self.clone(), camera, Some(effect),
+			texture, has_texture)
+	}
+
+	fn write(&self, connection: &mut D, sprite: &D::Sprite)
+		-> Result<(), Error>
+	{
+		connection.sprite_write(sprite, self)
+	}
+}
+
+/// Make a sprite with `uniform` for each frame in flight, each reading
+/// that frame's camera and fog.
+fn sprites<D: Device>(connection: &mut D, uniform: &dyn Uniform<D>,
+	style: &D::Style, cameras: &[(D::CameraMemory, D::EffectMemory)],
+	texture: Option<(D::Image, D::Sampler)>) -> Vec<D::Sprite>
+{
+	cameras.iter().map(|&(ref camera, ref effect)| {
+		uniform.sprite(connection, style, camera, effect,
+			texture.clone())
+	}).collect()
+}
+
+/// Make a camera and fog for as many frames as there can be in flight.
+fn new_cameras<D: Device>(connection: &mut D, (r, g, b): (f32, f32, f32),
+	fog: (f32, f32)) -> Vec<(D::CameraMemory, D::EffectMemory)>
+{
+	(0..MAX_FRAMES_IN_FLIGHT).map(|_| {
+		connection.camera((r, g, b, 1.0), fog)
+	}).collect()
+}
+
+/// Get what a shape binds to sample `texture`, making its sampler if no
+/// texture with the same `SamplerDesc` has been bound yet.
+fn bind_texture<D: Device>(connection: &mut D,
+	samplers: &mut HashMap<SamplerDesc, D::Sampler>, texture: &Texture<D>)
+	-> (D::Image, D::Sampler)
+{
+	let desc = texture.config.sampler;
+	let sampler = *samplers.entry(desc).or_insert_with(|| {
+		let anisotropy = desc.anisotropy.max(1)
+			.min(connection.max_anisotropy());
+
+		connection.sampler(&SamplerDesc { anisotropy, .. desc })
+	});
+	let image = texture.image.as_ref()
+		.or(texture.mappable_image.as_ref()).unwrap();
+
+	(image.clone(), sampler)
+}
+
+/// Handles of what a shape was made from, to make it again if the device is
+/// lost.
+#[derive(Copy, Clone)]
+struct Sources {
+	model: u32,
+	texture: Option<u32>,
+	texcoords: Option<u32>,
+	gradient: Option<u32>,
 }
 
-pub struct Shape {
+impl Sources {
+	/// Get every handle.
+	fn handles(&self) -> impl Iterator<Item = u32> {
+		Some(self.model).into_iter().chain(self.texture)
+			.chain(self.texcoords).chain(self.gradient)
+	}
+}
+
+pub struct Shape<D: Device> {
 	num_buffers: usize,
-	buffers: [VkBuffer; 3],
-	instance: Sprite,
+	buffers: [D::VertexBuffer; 3],
+	instances: Vec<D::Sprite>, // One for each frame in flight
+	stale: u32, // Frames (as bits) whose sprite has an old uniform
 	fans: Vec<(u32, u32)>,
 	transform: Transform, // Transformation matrix.
+	layer: i32, // GUI shapes on higher layers are drawn on top.
+	style: StyleId,
+	blending: bool,
+	hcam: u32, // fog as u32 + camera as u32
+	sources: Sources,
+	uniform: Box<dyn Uniform<D>>,
 }
 
-impl ::adi_gpu_base::Point for Shape {
+impl<D: Device> ::adi_gpu_base::Point for Shape<D> {
 	fn point(&self) -> Vec3 {
 		// Position vector at origin * object transform.
 		(self.transform.0 * vec4!(0f32, 0f32, 0f32, 1f32)).xyz()
 	}
 }
 
-pub struct Model {
-	shape: asi_vulkan::Buffer,
+/// A style made from the user's shaders.
+struct CustomStyle<D: Device> {
+	vert_spirv: Vec<u8>, // Kept to load again if the device is lost
+	frag_spirv: Vec<u8>,
+	vert: D::Shader,
+	frag: D::Shader,
+	style: D::Style,
+	nastyle: D::Style, // Without blending
+	textures: u32,
+	vertex_buffers: u32,
+}
+
+pub struct Model<D: Device> {
+	shape: D::Buffer,
 	vertex_count: u32,
 	fans: Vec<(u32, u32)>,
+	vertices: Vec<f32>, // Kept to upload again if the device is lost
 }
 
-pub struct TexCoords {
-	vertex_buffer: Buffer,
+pub struct TexCoords<D: Device> {
+	vertex_buffer: D::Buffer,
 	vertex_count: u32,
+	data: Vec<f32>, // Kept to upload again if the device is lost
 }
 
-pub struct Gradient {
-	vertex_buffer: Buffer,
+pub struct Gradient<D: Device> {
+	vertex_buffer: D::Buffer,
 	vertex_count: u32,
+	data: Vec<f32>, // Kept to upload again if the device is lost
 }
 
-impl Shape {
+impl<D: Device> Shape<D> {
 // TODO
 /*	pub fn animate(window: &mut Window, index: usize, i: usize,
 		texture: *const NativeTexture, style: Style)
@@ -139,68 +313,101 @@ impl Shape {
 	}*/
 }
 
-fn swapchain_resize(connection: &Gpu, image_count: &mut u32,
-	present_images: &mut [VkImage; 2],
-	present_image_views: &mut [VkImageView; 2],
-	frame_buffers: &mut [VkFramebuffer; 2])
-	-> (Image, Image, VkRenderPass)
+/// Pick the first present mode in the fallback chain for `wanted` that's
+/// supported.  Vulkan requires `Fifo` to always be supported.
+fn select_present_mode(wanted: PresentMode, supported: &[PresentMode])
+	-> PresentMode
 {
-	unsafe {
-		let submit_fence;
-		let depth_image;
-		let ms_image;
-		let render_pass;
-
-		// Link swapchain to vulkan instance.
-		asi_vulkan::create_swapchain(
-			connection,
-			image_count,
-			&mut present_images[0]
-		);
+	let chain: &[PresentMode] = match wanted {
+		PresentMode::Fifo => &[],
+		PresentMode::Mailbox => &[PresentMode::Mailbox],
+		PresentMode::Immediate => &[PresentMode::Immediate,
+			PresentMode::Mailbox],
+	};
 
-		// Link Image Views for each framebuffer
-		submit_fence = asi_vulkan::create_image_view(
-			connection,
-			*image_count,
-			present_images,
-			present_image_views,
-		);
+	chain.iter().cloned().find(|mode| supported.contains(mode))
+		.unwrap_or(PresentMode::Fifo)
+}
 
-		// Link Depth Buffer to swapchain
-		depth_image = asi_vulkan::create_depth_buffer(
-			connection,
-			&submit_fence,
-		);
+/// Clamp the requested image count to 2-4, and then to what the surface
+/// supports (a `max` of 0 means no limit).
+fn select_image_count(wanted: u32, (min, max): (u32, u32)) -> u32 {
+	let count = wanted.max(2).min(4).max(min);
+	let count = if max == 0 { count } else { count.min(max) };
 
-		// Create multisampling buffer
-		ms_image = asi_vulkan::create_ms_buffer(
-			connection,
-		);
+	// Only if the surface needs more images than any known one does.
+	count.min(MAX_IMAGES as u32)
+}
 
-		// Link Render Pass to swapchain
-		render_pass = asi_vulkan::create_render_pass(
-			connection,
-		);
+/// Pick the most samples per pixel up to `wanted` (rounded down to 1, 2, 4 or
+/// 8) that's in the `VkSampleCountFlags` `supported`.  1 is always supported.
+fn select_samples(wanted: u32, supported: u32) -> VkSampleCount {
+	let counts = [(8, VkSampleCount::Sc8), (4, VkSampleCount::Sc4),
+		(2, VkSampleCount::Sc2)];
 
-		// Link Framebuffers to swapchain
-		asi_vulkan::create_framebuffers(
-			connection,
-			*image_count,
-			render_pass,
-			present_image_views,
-			&ms_image,
-			&depth_image,
-			frame_buffers,
-		);
+	counts.iter().find(|&&(count, _)| count <= wanted
+		&& supported & count != 0)
+		.map(|&(_, samples)| samples)
+		.unwrap_or(VkSampleCount::Sc1)
+}
+
+/// Get the number of samples per pixel in `samples`.
+fn sample_count(samples: VkSampleCount) -> u32 {
+	match samples {
+		VkSampleCount::Sc1 => 1,
+		VkSampleCount::Sc2 => 2,
+		VkSampleCount::Sc4 => 4,
+		VkSampleCount::Sc8 => 8,
+	}
+}
+
+fn swapchain_resize<D: Device>(connection: &mut D,
+	offscreen: Option<(u16, u16)>, config: &DisplayConfig,
+	present_mode: &mut PresentMode, image_count: &mut u32,
+	samples: &mut VkSampleCount,
+	present_images: &mut [D::PresentImage; MAX_IMAGES],
+	present_image_views: &mut [D::ImageView; MAX_IMAGES],
+	frame_buffers: &mut [D::Framebuffer; MAX_IMAGES])
+	-> (Option<D::Image>, D::Image, Option<D::Image>, D::RenderPass)
+{
+	*samples = select_samples(config.samples, connection.sample_counts());
+
+	if let Some((width, height)) = offscreen {
+		// Render into a single offscreen image.
+		*image_count = 1;
+
+		let (color_image, depth_image, ms_image, render_pass)
+			= connection.offscreen_new(width, height, *samples,
+				present_images, present_image_views,
+				frame_buffers);
+
+		(Some(color_image), depth_image, ms_image, render_pass)
+	} else {
+		let supported = connection.present_modes();
+		let counts = connection.image_counts();
+
+		*present_mode = select_present_mode(config.present_mode,
+			&supported);
+		*image_count = select_image_count(config.image_count, counts);
 
-		(depth_image, ms_image, render_pass)
+		let (depth_image, ms_image, render_pass)
+			= connection.swapchain_new(*present_mode, image_count,
+				*samples, present_images, present_image_views,
+				frame_buffers);
+
+		(None, depth_image, ms_image, render_pass)
 	}
 }
 
-fn swapchain_delete(vw: &mut Vw) {
-	unsafe {
-		asi_vulkan::destroy_swapchain(
-			&vw.connection,
+fn swapchain_delete<D: Device>(vw: &mut Vw<D>) {
+	if vw.offscreen.is_some() {
+		vw.connection.offscreen_drop(
+			&vw.frame_buffers,
+			&vw.present_image_views,
+			vw.render_pass,
+		);
+	} else {
+		vw.connection.swapchain_drop(
 			&vw.frame_buffers,
 			&vw.present_image_views,
 			vw.render_pass,
@@ -209,858 +416,2035 @@ fn swapchain_delete(vw: &mut Vw) {
 	}
 }
 
-fn new_texture(vw: &mut Vw, width: u16, height: u16) -> Texture {
-//	let mut format_props = unsafe { mem::uninitialized() };
-	let staged = !vw.connection.sampled();
-
-	let mappable_image = asi_vulkan::Image::new(
-		&mut vw.connection, width as u32, height as u32,
-		VkFormat::R8g8b8a8Srgb, // Because VkColorSpace is always Srgb
-		VkImageTiling::Linear,
-		if staged { VkImageUsage::TransferSrcBit }
-		else { VkImageUsage::SampledBit },
-		VkImageLayout::Preinitialized,
-		0x00000006 /* visible|coherent */,
-		VkSampleCount::Sc1
-	);
-
-	let layout = unsafe {
-		asi_vulkan::subres_layout(&vw.connection, &mappable_image)
+/// Get the format of textures with `config`.
+fn texture_format(config: &TextureConfig) -> VkFormat {
+	match config.format {
+		TextureFormat::Rgba8 => match config.color_space {
+			ColorSpace::Srgb => VkFormat::R8g8b8a8Srgb,
+			ColorSpace::Linear => VkFormat::R8g8b8a8Unorm,
+		},
+		TextureFormat::R8 => VkFormat::R8Unorm,
+		TextureFormat::Rg8 => VkFormat::R8g8Unorm,
+		TextureFormat::Rgba16F => VkFormat::R16g16b16a16Sfloat,
+		TextureFormat::Rgba32F => VkFormat::R32g32b32a32Sfloat,
+		TextureFormat::Depth32F => VkFormat::D32Sfloat,
+	}
+}
+
+/// Whether mip levels of `format` can be made on the CPU, which only
+/// averages 8-bit channels.
+fn cpu_mipmaps(format: TextureFormat) -> bool {
+	match format {
+		TextureFormat::Rgba8 => true,
+		TextureFormat::R8 => true,
+		TextureFormat::Rg8 => true,
+		_ => false,
+	}
+}
+
+fn new_texture<D: Device>(vw: &mut Vw<D>, width: u16, height: u16,
+	config: TextureConfig) -> Result<Texture<D>, Error>
+{
+	let levels = if config.mipmaps {
+		mipmap::levels(width, height)
+	} else {
+		1
+	};
+	// Both images have the same format, so the texture looks the same
+	// whether or not it's staged.
+	let format = texture_format(&config);
+	// Mip levels are blitted from the level before.
+	let usage = if levels > 1 { VkImageUsage::TransferSrcDstAndUsage }
+		else { VkImageUsage::TransferDstAndUsage };
+
+	// Linear tiled images can only have one mip level, and depth can only
+	// be sampled with optimal tiling.
+	let depth = config.format == TextureFormat::Depth32F;
+	let staged = !vw.connection.sampled() || levels > 1 || depth
+		|| !vw.connection.format_supported(format,
+			VkImageTiling::Linear, VkImageUsage::SampledBit);
+
+	// Depth isn't staged through a linear tiled image, but a buffer.
+	if staged && !((depth || vw.connection.format_supported(format,
+		VkImageTiling::Linear, VkImageUsage::TransferSrcBit))
+		&& vw.connection.format_supported(format,
+			VkImageTiling::Optimal, usage))
+	{
+		return Err(Error::UnsupportedFormat);
+	}
+
+	if levels > 1 && !vw.connection.linear_blit(format)
+		&& !cpu_mipmaps(config.format)
+	{
+		return Err(Error::UnsupportedFormat);
+	}
+	let mappable_image = if depth {
+		None
+	} else {
+		Some(vw.connection.image(
+			width as u32, height as u32, 1,
+			format,
+			VkImageTiling::Linear,
+			if staged { VkImageUsage::TransferSrcBit }
+			else { VkImageUsage::SampledBit },
+			VkImageLayout::Preinitialized,
+			0x00000006 /* visible|coherent */,
+			VkSampleCount::Sc1
+		)?)
 	};
 
-	let pitch = layout.row_pitch;
+	let pitch = match mappable_image {
+		Some(ref image) => vw.connection.image_pitch(image),
+		None => 0,
+	};
 
 	let image = if staged {
-		Some(asi_vulkan::Image::new(
-			&mut vw.connection, width as u32, height as u32,
-			VkFormat::R8g8b8a8Unorm,
+		Some(vw.connection.image(
+			width as u32, height as u32, levels,
+			format,
 			VkImageTiling::Optimal,
-			VkImageUsage::TransferDstAndUsage,
+			usage,
 			VkImageLayout::Undefined, 0,
-			VkSampleCount::Sc1))
+			VkSampleCount::Sc1)?)
 	} else {
 		None
 	};
 
-	Texture {
-		staged, mappable_image,	image, pitch: pitch as u32,
+	Ok(Texture {
+		staged, mappable_image,	image, pitch, levels, config,
 		w: width, h: height,
-	}
+		rgba: Vec::new(),
+	})
 }
 
-fn set_texture(vw: &mut Vw, texture: &mut Texture, rgba: &[u8]) {
-	ffi::copy_memory_pitched(&mut vw.connection,
-		texture.image
-			.as_ref()
-			.unwrap_or(&texture.mappable_image)
-			.memory(),
-		rgba, texture.w as usize, texture.h as usize,
-		texture.pitch as usize);
+fn set_texture<D: Device>(vw: &mut Vw<D>, texture: &mut Texture<D>,
+	rgba: &[u8]) -> Result<(), Error>
+{
+	let bpp = texture.config.format.bytes_per_pixel();
+
+	if rgba.len() != texture.w as usize * texture.h as usize * bpp {
+		return Err(Error::InvalidRegion);
+	}
+
+	// The optimal tiled image (if staged) can't be mapped, so it's copied
+	// into from the mappable one.  Depth can't be copied from a linear
+	// tiled image, so it's written through a buffer instead.
+	match texture.mappable_image {
+		Some(ref mappable) => vw.connection.image_write(mappable, rgba,
+			texture.w as usize, texture.h as usize,
+			texture.pitch as usize, bpp)?,
+		None => vw.connection.image_write_depth(
+			texture.image.as_ref().unwrap(), rgba, (0, 0),
+			(texture.w, texture.h))?,
+	}
 
 	if texture.staged {
 		// Use optimal tiled image - create from linear tiled image
 
 		// Copy data from linear image to optimal image.
-		unsafe {
-			asi_vulkan::copy_image(&mut vw.connection,
-				&texture.mappable_image,
+		if let Some(ref mappable) = texture.mappable_image {
+			vw.connection.image_copy(mappable,
 				texture.image.as_ref().unwrap(),
-				texture.w, texture.h
-			);
+				texture.w, texture.h, 0);
+		}
+
+		if texture.levels > 1 {
+			mipmaps(vw, texture, rgba)?;
 		}
 	} else {
 		// Use a linear tiled image for the texture, is supported
 		texture.image = None;
 	}
+
+	texture.rgba = rgba.to_vec();
+	Ok(())
 }
 
-/*pub fn make_styles(vw: &mut Vw, extrashaders: &[Shader], shaders: &mut Vec<Style>)
+/// Replace a rectangle of a texture, which must fit inside it.
+fn set_texture_region<D: Device>(vw: &mut Vw<D>, texture: &mut Texture<D>,
+	x: u16, y: u16, width: u16, height: u16, rgba: &[u8])
+	-> Result<(), Error>
 {
-	let mut shadev = Vec::new();
-	let default_shaders = [
-//		Shader::create(vw, include_bytes!("res/texture-vert.spv"),
-//			include_bytes!("res/texture-frag.spv"), 1),
-	];
-	shadev.extend(default_shaders.iter().cloned());
-	shadev.extend(extrashaders.iter().cloned());
-
-	*shaders = vec![Style { pipeline: 0, descsetlayout: 0,
-		pipeline_layout: 0 }; shadev.len()];
-	unsafe {
-		vw_vulkan_pipeline(&mut shaders[0], vw, &shadev[0],
-			shadev.len() as u32);
-	}
-}*/
-
-impl Vw {
-	pub fn new(window: Option<(&str, &Video)>, rgb: Vec3)
-		-> Result<(Vw, Window), String>
-	{
-		let (mut connection, window)
-			= asi_vulkan::Gpu::new(window, rgb)?;
+	let bpp = texture.config.format.bytes_per_pixel();
+
+	match texture.mappable_image {
+		Some(ref mappable) => {
+			vw.connection.image_write_region(mappable, rgba,
+				(x, y), (width, height),
+				texture.pitch as usize, bpp)?;
+
+			if let Some(ref image) = texture.image {
+				vw.connection.image_copy_region(mappable,
+					image, x, y, width, height);
+			}
+		}
+		None => vw.connection.image_write_depth(
+			texture.image.as_ref().unwrap(), rgba, (x, y),
+			(width, height))?,
+	}
+
+	// Keep the CPU copy up to date, to make mip levels from (and
+	// upload again if the device is lost).
+	let len = width as usize * bpp;
+	for row in 0..height as usize {
+		let from = row * len;
+		let to = ((y as usize + row) * texture.w as usize + x as usize)
+			* bpp;
+
+		texture.rgba[to..to + len]
+			.copy_from_slice(&rgba[from..from + len]);
+	}
+
+	if texture.levels > 1 {
+		mipmaps(vw, texture, &texture.rgba)?;
+	}
+
+	Ok(())
+}
+
+/// Fill in every mip level after the first, by blitting on the GPU, or if
+/// the format can't be blitted with filtering, by averaging on the CPU.
+fn mipmaps<D: Device>(vw: &mut Vw<D>, texture: &Texture<D>, rgba: &[u8])
+	-> Result<(), Error>
+{
+	let image = texture.image.as_ref().unwrap();
+
+	if vw.connection.linear_blit(texture_format(&texture.config)) {
+		vw.connection.image_mipmaps(image, texture.w, texture.h,
+			texture.levels);
+		return Ok(());
+	}
+
+	let bpp = texture.config.format.bytes_per_pixel();
+	let srgb = texture.config.format == TextureFormat::Rgba8
+		&& texture.config.color_space == ColorSpace::Srgb;
+	let (mut width, mut height) = (texture.w, texture.h);
+	let mut pixels = rgba.to_vec();
+
+	// The levels are staged in their own image (big enough for level 1),
+	// so the mappable one keeps level 0 for `set_texture_region()`.
+	let (w, h) = mipmap::next_size(width, height);
+	let staging = vw.connection.image(w as u32, h as u32, 1,
+		texture_format(&texture.config), VkImageTiling::Linear,
+		VkImageUsage::TransferSrcBit, VkImageLayout::Preinitialized,
+		0x00000006 /* visible|coherent */, VkSampleCount::Sc1)?;
+	let pitch = vw.connection.image_pitch(&staging) as usize;
+	let mut result = Ok(());
+
+	for level in 1..texture.levels {
+		pixels = mipmap::half(&pixels, width, height, bpp, srgb);
+		let (w, h) = mipmap::next_size(width, height);
+		width = w;
+		height = h;
+
+		result = vw.connection.image_write(&staging, &pixels,
+			width as usize, height as usize, pitch, bpp);
+		if result.is_err() {
+			break;
+		}
+		vw.connection.image_copy(&staging, image, width, height,
+			level);
+	}
+
+	vw.connection.image_drop(staging);
+	result
+}
+
+/// Make a pipeline cache, seeded from the last run if `config` keeps one.
+fn new_pipeline_cache<D: Device>(connection: &mut D, config: &DisplayConfig)
+	-> D::PipelineCache
+{
+	let data = match config.pipeline_cache {
+		Some(ref dir) => {
+			cache::load(dir, connection.pipeline_cache_id())
+		}
+		None => Vec::new(),
+	};
+
+	connection.pipeline_cache(&data)
+}
 
+impl<D: Device> Vw<D> {
+	/// Create a swapchain for `connection` with `config`, or if
+	/// `offscreen` is `Some`, an offscreen image of that size.
+	pub fn new(mut connection: D, offscreen: Option<(u16, u16)>,
+		config: DisplayConfig) -> Vw<D>
+	{
 		// END BLOCK 2
-		let mut image_count = unsafe {
-			asi_vulkan::get_buffering(&mut connection)
-		};
+		let mut image_count = 0;
+		let mut present_mode = PresentMode::Fifo;
+		let mut samples = VkSampleCount::Sc1;
 
 		// Prepare Swapchain
-		let mut present_images: [VkImage; 2] = [unsafe { mem::zeroed() }; 2];
-		let mut present_image_views = [unsafe { mem::zeroed() }; 2];
-		let mut frame_buffers: [VkFramebuffer; 2]
-			= [unsafe { mem::uninitialized() }; 2];
-
-		let (depth_image, ms_image, render_pass)
-			= swapchain_resize(&connection,
-				&mut image_count,
-				&mut present_images,
+		let mut present_images
+			= [D::PresentImage::default(); MAX_IMAGES];
+		let mut present_image_views
+			= [D::ImageView::default(); MAX_IMAGES];
+		let mut frame_buffers = [D::Framebuffer::default(); MAX_IMAGES];
+
+		let (offscreen, depth_image, ms_image, render_pass)
+			= swapchain_resize(&mut connection, offscreen, &config,
+				&mut present_mode, &mut image_count,
+				&mut samples, &mut present_images,
 				&mut present_image_views, &mut frame_buffers);
 
-		let vw = Vw {
+		let pipeline_cache = new_pipeline_cache(&mut connection,
+			&config);
+
+		Vw {
 			connection,
 			present_images, frame_buffers,
 			image_count,
-			present_image_views, ms_image, depth_image, render_pass,
-		};
+			present_image_views, offscreen,
+			config, present_mode, samples,
+			ms_image, depth_image, render_pass,
+			pipeline_cache,
+		}
+	}
+
+	/// Whether objects are named for validation messages.
+	fn debug(&self) -> bool {
+		self.config.debug != DebugMode::Off
+	}
+}
+
+/// A dropped GPU resource, waiting to be freed.
+enum Garbage<D: Device> {
+	Buffer(D::Buffer),
+	Image(D::Image),
+	Sprite(D::Sprite),
+}
+
+fn free_garbage<D: Device>(connection: &mut D, garbage: Vec<Garbage<D>>) {
+	for garbage in garbage {
+		match garbage {
+			Garbage::Buffer(buffer) => connection.buffer_drop(buffer),
+			Garbage::Image(image) => connection.image_drop(image),
+			Garbage::Sprite(sprite) => connection.sprite_drop(sprite),
+		}
+	}
+}
+
+/// Command buffer and synchronization for one frame in flight.
+struct Frame<D: Device> {
+	command_buffer: D::CommandBuffer,
+	image_available: D::Semaphore, // Signaled when image is acquired
+	render_finished: D::Semaphore, // Signaled when image can be presented
+	in_flight: D::Fence, // Signaled when command buffer can be reused
+	submitted: bool, // Whether `in_flight` is going to be signaled
+	garbage: Vec<Garbage<D>>, // Freed once `in_flight` is signaled
+	textures: HashSet<u32>, // Drawn, so not written until it's signaled
+}
+
+fn new_frames<D: Device>(connection: &mut D, count: usize) -> Vec<Frame<D>> {
+	(0..count).map(|_| Frame {
+		command_buffer: connection.command_buffer(),
+		image_available: connection.semaphore(),
+		render_finished: connection.semaphore(),
+		in_flight: connection.fence(),
+		submitted: false,
+		garbage: Vec::new(),
+		textures: HashSet::new(),
+	}).collect()
+}
+
+/// Wait for a frame in flight to finish.  Its garbage is freed even if
+/// that fails.
+fn wait_frame<D: Device>(connection: &mut D, frame: &mut Frame<D>)
+	-> Result<(), Error>
+{
+	let result = if frame.submitted {
+		frame.submitted = false;
+		connection.fence_wait(&frame.in_flight)
+	} else {
+		Ok(())
+	};
+
+	let garbage = mem::replace(&mut frame.garbage, Vec::new());
+	free_garbage(connection, garbage);
+	frame.textures.clear();
+
+	result
+}
+
+/// Wait for every frame in flight to finish.  Returns the first error, but
+/// still frees every frame's garbage.
+fn wait_frames<D: Device>(connection: &mut D, frames: &mut [Frame<D>])
+	-> Result<(), Error>
+{
+	let mut result = Ok(());
+
+	for frame in frames.iter_mut() {
+		result = result.and(wait_frame(connection, frame));
+	}
+
+	result
+}
+
+fn drop_frames<D: Device>(connection: &mut D, mut frames: Vec<Frame<D>>) {
+	// If the device is lost there's nothing left to wait for.
+	let _ = wait_frames(connection, &mut frames);
+
+	for frame in frames.drain(..) {
+		connection.command_buffer_drop(frame.command_buffer);
+		connection.semaphore_drop(frame.image_available);
+		connection.semaphore_drop(frame.render_finished);
+		connection.fence_drop(frame.in_flight);
+	}
+}
+
+/// Convert a `Model`, `Texture`, `TexCoords` or `Gradient` id to a handle.
+fn key(id: usize) -> u32 {
+	// Too big to be a handle, 0 is never valid.
+	if id > ::std::u32::MAX as usize { 0 } else { id as u32 }
+}
+
+/// Check `shaders` against the uniform type `U`, then make a style from them.
+/// `build.rs` already checked them, so this only fails if they're changed
+/// without building again.
+fn new_style<D: Device, U>(vw: &mut Vw<D>, shaders: &Shaders, alpha: bool)
+	-> Result<D::Style, Error>
+{
+	let frag = if alpha { shaders.bfrag } else { shaders.frag };
+
+	spirv::check(shaders.name, shaders.vert, frag, mem::size_of::<U>(),
+		shaders.textures, shaders.vertex_buffers)
+		.map_err(Error::InvalidShader)?;
+
+	let vert = vw.connection.shader(shaders.vert);
+	let frag = vw.connection.shader(frag);
+
+	let style = vw.connection.style(vw.render_pass, &vw.pipeline_cache,
+		&vert, &frag, shaders.textures, shaders.vertex_buffers, alpha,
+		vw.samples);
+
+	if vw.debug() {
+		vw.connection.name_style(&style, &format!("{} style{}",
+			shaders.name, if alpha { " (blended)" } else { "" }));
+	}
+
+	Ok(style)
+}
+
+/// Make a custom style's styles, with and without blending, for the current
+/// render pass.
+fn custom_style<D: Device>(vw: &mut Vw<D>, handle: u32,
+	custom: &mut CustomStyle<D>)
+{
+	custom.style = vw.connection.style(vw.render_pass, &vw.pipeline_cache,
+		&custom.vert, &custom.frag, custom.textures,
+		custom.vertex_buffers, true, vw.samples);
+	custom.nastyle = vw.connection.style(vw.render_pass,
+		&vw.pipeline_cache, &custom.vert, &custom.frag,
+		custom.textures, custom.vertex_buffers, false, vw.samples);
+
+	name_custom_style(vw, handle, custom);
+}
+
+fn name_custom_style<D: Device>(vw: &mut Vw<D>, handle: u32,
+	custom: &CustomStyle<D>)
+{
+	if vw.debug() {
+		let name = format!("Custom style {:#x}", handle);
+
+		vw.connection.name_style(&custom.style,
+			&format!("{} (blended)", name));
+		vw.connection.name_style(&custom.nastyle, &name);
+	}
+}
+
+/// Name a texture's images, in debug mode.
+fn name_texture<D: Device>(vw: &mut Vw<D>, handle: u32,
+	texture: &Texture<D>)
+{
+	if vw.debug() {
+		let name = format!("Texture {:#x}", handle);
+
+		if let Some(ref image) = texture.mappable_image {
+			vw.connection.name_image(image, &name);
+		}
+		if let Some(ref image) = texture.image {
+			vw.connection.name_image(image, &name);
+		}
+	}
+}
 
-		Ok((vw, window))
+/// Name a model's vertex buffer, in debug mode.
+fn name_model<D: Device>(vw: &mut Vw<D>, handle: u32, model: &Model<D>) {
+	if vw.debug() {
+		vw.connection.name_buffer(&model.shape,
+			&format!("Model {:#x}", handle));
 	}
 }
 
-fn draw_shape(connection: &Gpu, shape: &Shape) {
-	unsafe {
-		// TODO: reduce calls to these functions (for speed).
-		asi_vulkan::cmd_bind_vb(connection,
-			&shape.buffers[..shape.num_buffers]);
-		asi_vulkan::cmd_bind_pipeline(connection,
-			shape.instance.pipeline);
-		asi_vulkan::cmd_bind_descsets(connection,
-			shape.instance.pipeline_layout,
-			shape.instance.handles().0/*desc_set*/);
+/// Which style a shape was made with.
+#[derive(Copy, Clone, PartialEq)]
+enum StyleId {
+	Solid,
+	Texture,
+	Gradient,
+	Faded,
+	Tinted,
+	Complex,
+	Custom(u32), // Handle in `Renderer.custom_styles`
+}
+
+/// The built-in styles.  The `na` ones are for shapes without blending.
+struct Styles<D: Device> {
+	solid: D::Style,
+	nasolid: D::Style,
+	texture: D::Style,
+	natexture: D::Style,
+	gradient: D::Style,
+	nagradient: D::Style,
+	faded: D::Style,
+	tinted: D::Style,
+	natinted: D::Style,
+	complex: D::Style,
+	nacomplex: D::Style,
+}
+
+impl<D: Device> Styles<D> {
+	/// Make every built-in style for the current render pass.
+	fn new(vw: &mut Vw<D>) -> Result<Styles<D>, Error> {
+		Ok(Styles {
+			solid: new_style::<D, TransformAndColorUniform>(vw,
+				&SOLID, true)?,
+			nasolid: new_style::<D, TransformAndColorUniform>(vw,
+				&SOLID, false)?,
+			texture: new_style::<D, TransformFullUniform>(vw,
+				&TEXTURE, true)?,
+			natexture: new_style::<D, TransformFullUniform>(vw,
+				&TEXTURE, false)?,
+			gradient: new_style::<D, TransformFullUniform>(vw,
+				&GRADIENT, true)?,
+			nagradient: new_style::<D, TransformFullUniform>(vw,
+				&GRADIENT, false)?,
+			faded: new_style::<D, TransformAndFadeUniform>(vw,
+				&FADED, true)?,
+			tinted: new_style::<D, TransformAndColorUniform>(vw,
+				&TINTED, true)?,
+			natinted: new_style::<D, TransformAndColorUniform>(vw,
+				&TINTED, false)?,
+			complex: new_style::<D, TransformFullUniform>(vw,
+				&COMPLEX, true)?,
+			nacomplex: new_style::<D, TransformFullUniform>(vw,
+				&COMPLEX, false)?,
+		})
+	}
+}
+
+/// Get the kind of handle a shape gets: GUI if it doesn't use the camera or
+/// fog, and otherwise alpha or opaque.
+pub fn shape_kind(alpha: bool, fog: bool, camera: bool)
+	-> fn(u32) -> ShapeHandle
+{
+	if !camera && !fog {
+		ShapeHandle::Gui
+	} else if alpha {
+		ShapeHandle::Alpha
+	} else {
+		ShapeHandle::Opaque
+	}
+}
 
-		for i in shape.fans.iter() {
-			asi_vulkan::cmd_draw(connection, i.1,
-				1, i.0, 0);
+/// Get the style a shape draws with.
+fn shape_style<'a, D: Device>(styles: &'a Styles<D>,
+	custom_styles: &'a Slots<CustomStyle<D>>, shape: &Shape<D>)
+	-> &'a D::Style
+{
+	match (shape.style, shape.blending) {
+		(StyleId::Solid, true) => &styles.solid,
+		(StyleId::Solid, false) => &styles.nasolid,
+		(StyleId::Texture, true) => &styles.texture,
+		(StyleId::Texture, false) => &styles.natexture,
+		(StyleId::Gradient, true) => &styles.gradient,
+		(StyleId::Gradient, false) => &styles.nagradient,
+		(StyleId::Faded, _) => &styles.faded,
+		(StyleId::Tinted, true) => &styles.tinted,
+		(StyleId::Tinted, false) => &styles.natinted,
+		(StyleId::Complex, true) => &styles.complex,
+		(StyleId::Complex, false) => &styles.nacomplex,
+		(StyleId::Custom(style), blending) => {
+			// Custom styles are never dropped.
+			let custom = custom_styles.get(style).unwrap();
+
+			if blending {
+				&custom.style
+			} else {
+				&custom.nastyle
+			}
 		}
 	}
 }
 
-pub struct Renderer {
-	vw: Vw,
+/// Draw a shape with its sprite for frame `frame`, which reads `textures`.
+fn draw_shape<D: Device>(connection: &mut D, shape: &Shape<D>, frame: usize,
+	textures: &mut HashSet<u32>)
+{
+	// TODO: reduce calls to these functions (for speed).
+	connection.bind_vb(&shape.buffers[..shape.num_buffers]);
+	connection.bind_sprite(&shape.instances[frame]);
+
+	for i in shape.fans.iter() {
+		connection.draw(i.1, i.0);
+	}
+
+	if let Some(texture) = shape.sources.texture {
+		textures.insert(texture);
+	}
+}
+
+pub struct Renderer<D: Device> {
+	vw: Vw<D>,
 	ar: f32,
 	opaque_ind: Vec<u32>,
 	alpha_ind: Vec<u32>,
-	opaque_vec: Vec<Shape>,
-	alpha_vec: Vec<Shape>,
-	gui_vec: Vec<Shape>,
-	models: Vec<Model>,
-	texcoords: Vec<TexCoords>,
-	gradients: Vec<Gradient>,
-	textures: Vec<Texture>,
-	style_solid: Style,
-	style_nasolid: Style,
-	style_texture: Style,
-	style_natexture: Style,
-	style_gradient: Style,
-	style_nagradient: Style,
-	style_faded: Style,
-	style_tinted: Style,
-	style_natinted: Style,
-	style_complex: Style,
-	style_nacomplex: Style,
+	gui_ind: Vec<u32>,
+	opaque_vec: Slots<Shape<D>>,
+	alpha_vec: Slots<Shape<D>>,
+	gui_vec: Slots<Shape<D>>,
+	models: Slots<Model<D>>,
+	texcoords: Slots<TexCoords<D>>,
+	gradients: Slots<Gradient<D>>,
+	textures: Slots<Texture<D>>,
+	samplers: HashMap<SamplerDesc, D::Sampler>, // Shared between textures
+	users: HashMap<u32, u32>, // Number of shapes made from each handle
+	custom_styles: Slots<CustomStyle<D>>,
+	styles: Styles<D>,
 	projection: Transform,
-	camera_memory: asi_vulkan::Memory<TransformUniform>,
-	effect_memory: asi_vulkan::Memory<FogUniform>,
+	// A camera and fog for each frame in flight, so writing one doesn't
+	// wait for the others.
+	cameras: Vec<(D::CameraMemory, D::EffectMemory)>,
+	view: [f32; 16], // Camera matrix, for the frames with a stale one
+	stale: u32, // Frames (as bits) whose camera and fog are old
 	clear_color: (f32, f32, f32),
+	fog: (f32, f32),
+	frames: Vec<Frame<D>>,
+	frame: usize, // Index of the next frame in `frames`
+	last_image: Option<u32>, // Index of the last rendered present image
+	minimized: bool, // Nothing is drawn while the window is 0x0
+	lost: bool, // The device was lost, and hasn't been rebuilt yet
 	xyz: Vec3,
 	rotate_xyz: Vec3,
 }
 
-impl Renderer {
-	pub fn new(window: Option<(&str, &Video)>, mut rgb: Vec3)
-		-> Result<(Renderer, Window), String>
+impl Renderer<Gpu> {
+	pub fn new(window: Option<(&str, &Video)>, rgb: Vec3,
+		config: DisplayConfig)
+		-> Result<(Renderer<Gpu>, Window), Error>
+	{
+		let adapter = ::adapter::select(&config.adapter)?;
+		let (connection, window) = Gpu::new(window, rgb, adapter,
+			::debug::callback(config.debug)).map_err(Error::Init)?;
+		let ar = connection.ar();
+
+		Ok((Renderer::with_device(connection, rgb, ar, None, config)?,
+			window))
+	}
+
+	/// Create a renderer without a window, that renders into an offscreen
+	/// image.
+	pub fn new_headless(width: u16, height: u16, rgb: Vec3,
+		config: DisplayConfig) -> Result<Renderer<Gpu>, Error>
+	{
+		let adapter = ::adapter::select(&config.adapter)?;
+		let connection = Gpu::headless(rgb, adapter,
+			::debug::callback(config.debug)).map_err(Error::Init)?;
+
+		Renderer::with_device(connection, rgb,
+			width as f32 / height as f32, Some((width, height)),
+			config)
+	}
+}
+
+impl<D: Device> Renderer<D> {
+	/// Create a renderer that draws with `connection`, into an offscreen
+	/// image if `offscreen` is `Some`.
+	pub fn with_device(connection: D, rgb: Vec3, ar: f32,
+		offscreen: Option<(u16, u16)>, config: DisplayConfig)
+		-> Result<Renderer<D>, Error>
 	{
-		let (mut vw, window) = Vw::new(window, rgb)?;
-
-		let solid_vert = asi_vulkan::ShaderModule::new(
-			&mut vw.connection, include_bytes!(
-			"../shaders/res/solid-vert.spv"));
-		let solid_frag = asi_vulkan::ShaderModule::new(
-			&mut vw.connection, include_bytes!(
-			"../shaders/res/solid-frag.spv"));
-		let texture_vert = asi_vulkan::ShaderModule::new(
-			&mut vw.connection, include_bytes!(
-			"../shaders/res/texture-vert.spv"));
-		let texture_frag = asi_vulkan::ShaderModule::new(
-			&mut vw.connection, include_bytes!(
-			"../shaders/res/texture-frag.spv"));
-		let gradient_vert = asi_vulkan::ShaderModule::new(
-			&mut vw.connection, include_bytes!(
-			"../shaders/res/gradient-vert.spv"));
-		let gradient_frag = asi_vulkan::ShaderModule::new(
-			&mut vw.connection, include_bytes!(
-			"../shaders/res/gradient-frag.spv"));
-		let faded_vert = asi_vulkan::ShaderModule::new(
-			&mut vw.connection, include_bytes!(
-			"../shaders/res/faded-vert.spv"));
-		let faded_frag = asi_vulkan::ShaderModule::new(
-			&mut vw.connection, include_bytes!(
-			"../shaders/res/faded-frag.spv"));
-		let tinted_vert = asi_vulkan::ShaderModule::new(
-			&mut vw.connection, include_bytes!(
-			"../shaders/res/gradient-vert.spv"));
-		let tinted_frag = asi_vulkan::ShaderModule::new(
-			&mut vw.connection, include_bytes!(
-			"../shaders/res/gradient-frag.spv"));
-		let complex_vert = asi_vulkan::ShaderModule::new(
-			&mut vw.connection, include_bytes!(
-			"../shaders/res/gradient-vert.spv"));
-		let complex_frag = asi_vulkan::ShaderModule::new(
-			&mut vw.connection, include_bytes!(
-			"../shaders/res/gradient-frag.spv"));
-		let style_solid = Style::new(&mut vw.connection, vw.render_pass,
-			&solid_vert, &solid_frag, 0, 1, true);
-		let style_nasolid = Style::new(&mut vw.connection,
-			vw.render_pass,	&solid_vert, &solid_frag, 0, 1, false);
-		let style_texture = Style::new(&mut vw.connection,
-			vw.render_pass,	&texture_vert, &texture_frag, 1, 2,
-			true);
-		let style_natexture = Style::new(&mut vw.connection,
-			vw.render_pass,	&texture_vert, &texture_frag, 1, 2,
-			false);
-		let style_gradient = Style::new(&mut vw.connection,
-			vw.render_pass,	&gradient_vert, &gradient_frag, 0, 2,
-			true);
-		let style_nagradient = Style::new(&mut vw.connection,
-			vw.render_pass,	&gradient_vert, &gradient_frag, 0, 2,
-			false);
-		let style_faded = Style::new(&mut vw.connection, vw.render_pass,
-			&faded_vert, &faded_frag, 1, 2, true);
-		let style_tinted = Style::new(&mut vw.connection,
-			vw.render_pass,	&tinted_vert, &tinted_frag, 1, 2, true);
-		let style_natinted = Style::new(&mut vw.connection,
-			vw.render_pass, &tinted_vert, &tinted_frag, 1, 2,
-			false);
-		let style_complex = Style::new(&mut vw.connection,
-			vw.render_pass, &complex_vert, &complex_frag, 1, 3,
-			true);
-		let style_nacomplex = Style::new(&mut vw.connection,
-			vw.render_pass, &complex_vert, &complex_frag, 1, 3,
-			false);
-
-		let ar = vw.connection.ar();
+		let mut vw = Vw::new(connection, offscreen, config);
+
+		let styles = Styles::new(&mut vw)?;
+
 		let projection = ::base::projection(ar, 0.5 * PI);
-		let (camera_memory, effect_memory) = unsafe {
-			asi_vulkan::vw_camera_new(&mut vw.connection,
-				(rgb.x, rgb.y, rgb.z, 1.0),
-				(::std::f32::MAX, ::std::f32::MAX))
-		};
+		let cameras = new_cameras(&mut vw.connection,
+			(rgb.x, rgb.y, rgb.z),
+			(::std::f32::MAX, ::std::f32::MAX));
+
+		let frames = new_frames(&mut vw.connection, FRAMES_IN_FLIGHT);
 
 		let mut renderer = Renderer {
-			vw, ar, projection,
-			camera_memory, effect_memory,
+			vw, ar, projection, cameras,
+			view: Transform::IDENTITY.into(),
+			stale: 0,
 			alpha_ind: Vec::new(),
 			opaque_ind: Vec::new(),
-			alpha_vec: Vec::new(),
-			opaque_vec: Vec::new(),
-			gui_vec: Vec::new(),
-			gradients: Vec::new(),
-			models: Vec::new(),
-			texcoords: Vec::new(),
-			textures: Vec::new(),
-			style_solid, style_nasolid,
-			style_texture, style_natexture,
-			style_gradient, style_nagradient,
-			style_faded,
-			style_tinted, style_natinted,
-			style_complex, style_nacomplex,
+			gui_ind: Vec::new(),
+			alpha_vec: Slots::new(Kind::Alpha),
+			opaque_vec: Slots::new(Kind::Opaque),
+			gui_vec: Slots::new(Kind::Gui),
+			gradients: Slots::new(Kind::Gradient),
+			models: Slots::new(Kind::Model),
+			texcoords: Slots::new(Kind::TexCoords),
+			textures: Slots::new(Kind::Texture),
+			samplers: HashMap::new(),
+			users: HashMap::new(),
+			custom_styles: Slots::new(Kind::Style),
+			styles,
 			clear_color: (rgb.x, rgb.y, rgb.z),
+			fog: (::std::f32::MAX, ::std::f32::MAX),
+			frames,
+			frame: 0,
+			last_image: None,
+			minimized: false,
+			lost: false,
 			xyz: vec3!(0.0, 0.0, 0.0),
 			rotate_xyz: vec3!(0.0, 0.0, 0.0),
 		};
 
 		renderer.camera();
 
-		Ok((renderer, window))
+		Ok(renderer)
 	}
 
 	pub fn bg_color(&mut self, rgb: Vec3) {
 		self.clear_color = (rgb.x, rgb.y, rgb.z);
 		self.vw.connection.color(rgb);
-		self.fog_color(rgb.x, rgb.y, rgb.z);
+		// The fog is the same color.
+		self.stale = self.every_frame();
 	}
 
-	pub fn update(&mut self) {
-		let rendering_complete_sem = unsafe {
-			asi_vulkan::new_semaphore(&self.vw.connection)
-		};
+	/// Draw and present a frame.  If the surface is lost, `resize()` must
+	/// be called before the next frame.
+	pub fn update(&mut self) -> Result<(), Error> {
+		// Half made again, so `rebuild()` has to be tried again.
+		if self.lost {
+			return Err(Error::DeviceLost);
+		}
+
+		// There's no swapchain to draw into.
+		if self.minimized {
+			return Ok(());
+		}
+
+		// Wait for the GPU to be done with this frame's command buffer
+		// and uniforms from last time around, but not for the frames
+		// after it.  Resources dropped while it was in flight go too.
+		wait_frame(&mut self.vw.connection,
+			&mut self.frames[self.frame])?;
+		self.vw.connection.fence_reset(
+			&self.frames[self.frame].in_flight);
+
+		// Now its own copies can be brought up to date.
+		let index = self.frame;
+		self.write_frame(index)?;
+
+		let frame = &mut self.frames[self.frame];
 
-		let fence = unsafe { asi_vulkan::fence::new(&self.vw.connection) };
-		let next_image_index = unsafe {
-			asi_vulkan::get_next_image(&self.vw.connection, fence)
+		// A suboptimal swapchain can still be drawn to, then made again
+		// after presenting.
+		let (next_image_index, suboptimal) = if self.vw.offscreen
+			.is_some()
+		{
+			(0, false)
+		} else {
+			self.vw.connection.next_image(&frame.image_available)?
 		};
 
-		unsafe {
-			asi_vulkan::draw_begin(&self.vw.connection,
-				self.vw.render_pass,
-				self.vw.present_images[next_image_index as usize],
-				self.vw.frame_buffers[next_image_index as usize]
-			);
-		}
+		self.vw.connection.draw_begin(
+			&frame.command_buffer,
+			self.vw.render_pass,
+			self.vw.present_images[next_image_index as usize],
+			self.vw.frame_buffers[next_image_index as usize]
+		);
 
 		// sort nearest
-		::adi_gpu_base::zsort(&mut self.opaque_ind, &self.opaque_vec,
-			true, self.xyz);
+		::adi_gpu_base::zsort(&mut self.opaque_ind,
+			self.opaque_vec.slots(), true, self.xyz);
 		for shape in self.opaque_ind.iter() {
-			let shape = &self.opaque_vec[*shape as usize];
-			draw_shape(&self.vw.connection, shape);
+			let shape = self.opaque_vec.at(*shape as usize);
+			draw_shape(&mut self.vw.connection, shape, index,
+				&mut frame.textures);
 		}
 
 		// sort farthest
-		::adi_gpu_base::zsort(&mut self.alpha_ind, &self.alpha_vec,
-			false, self.xyz);
+		::adi_gpu_base::zsort(&mut self.alpha_ind,
+			self.alpha_vec.slots(), false, self.xyz);
 		for shape in self.alpha_ind.iter() {
-			let shape = &self.alpha_vec[*shape as usize];
-			draw_shape(&self.vw.connection, shape);
+			let shape = self.alpha_vec.at(*shape as usize);
+			draw_shape(&mut self.vw.connection, shape, index,
+				&mut frame.textures);
 		}
 
 		// No need to sort gui elements.
-		for shape in self.gui_vec.iter() {
-			draw_shape(&self.vw.connection, shape);
+		for shape in self.gui_ind.iter() {
+			let shape = self.gui_vec.at(*shape as usize);
+			draw_shape(&mut self.vw.connection, shape, index,
+				&mut frame.textures);
 		}
 
-		unsafe {
-			asi_vulkan::end_render_pass(&self.vw.connection);
+		let present = if self.vw.offscreen.is_some() {
+			// Nothing to present.
+			self.vw.connection.draw_end(None);
+			self.vw.connection.queue_submit(&frame.command_buffer,
+				None, None, &frame.in_flight)?;
+			Ok(())
+		} else {
+			self.vw.connection.draw_end(Some(
+				self.vw.present_images[next_image_index as usize]));
+			self.vw.connection.queue_submit(&frame.command_buffer,
+				Some(&frame.image_available),
+				Some(&frame.render_finished),
+				&frame.in_flight)?;
 
-			asi_vulkan::pipeline_barrier(&self.vw.connection,
-				self.vw.present_images[next_image_index as usize]);
+			// Actually present the image to the screen.
+			self.vw.connection.queue_present(next_image_index,
+				&frame.render_finished)
+		};
 
-			asi_vulkan::end_cmdbuff(&self.vw.connection);
+		// The frame was submitted even if presenting it failed.
+		frame.submitted = true;
+		self.frame = (self.frame + 1) % self.frames.len();
+		self.last_image = Some(next_image_index);
+		match present {
+			Ok(()) if suboptimal => Err(Error::SurfaceLost),
+			present => present,
 		}
+	}
+
+	/// Get the present mode that was selected for the swapchain.
+	pub fn present_mode(&self) -> PresentMode {
+		self.vw.present_mode
+	}
+
+	/// Get the number of images rendered into.
+	pub fn image_count(&self) -> u32 {
+		self.vw.image_count
+	}
 
-		unsafe { // Drop fence when it's done use
-			let fence = asi_vulkan::Fence::new(&self.vw.connection);
+	/// Set how many frames the CPU may get ahead of the GPU, clamped to
+	/// 2 or 3.  More frames in flight means more latency, but less time
+	/// spent waiting on the GPU.
+	pub fn frames_in_flight(&mut self, count: usize) {
+		let count = count.max(MIN_FRAMES_IN_FLIGHT)
+			.min(MAX_FRAMES_IN_FLIGHT);
 
-			asi_vulkan::queue_submit(&self.vw.connection,
-				&fence,
-				VkPipelineStage::BottomOfPipe,
-				Some(rendering_complete_sem));
-				
-			asi_vulkan::wait_fence(&self.vw.connection, &fence);
+		if count == self.frames.len() {
+			return;
 		}
 
-		unsafe {
-			// Actually present the image to the screen.
-			asi_vulkan::queue_present(&self.vw.connection,
-				next_image_index);
+		let frames = mem::replace(&mut self.frames, Vec::new());
+		drop_frames(&mut self.vw.connection, frames);
+		self.frames = new_frames(&mut self.vw.connection, count);
+		self.frame = 0;
+
+		// Each frame has its own sprite for every shape, and camera.
+		self.resprite();
+		self.stale = self.every_frame();
+	}
+
+	/// Get the number of samples per pixel that was actually selected.
+	pub fn samples(&self) -> u32 {
+		sample_count(self.vw.samples)
+	}
 
-			asi_vulkan::fence::wait(&self.vw.connection, fence);
-			asi_vulkan::fence::drop(&self.vw.connection, fence);
+	/// Set how many samples per pixel to multisample with, remaking the
+	/// swapchain (or offscreen image) and styles if that changes which is
+	/// selected.  `size` is the current size.
+	pub fn set_samples(&mut self, samples: u32, size: (u16, u16))
+		-> Result<(), Error>
+	{
+		self.vw.config.samples = samples;
 
-			asi_vulkan::drop_semaphore(&self.vw.connection,
-				rendering_complete_sem);
+		let supported = self.vw.connection.sample_counts();
+		if select_samples(samples, supported) == self.vw.samples {
+			return Ok(());
+		}
 
-			asi_vulkan::wait_idle(&self.vw.connection);
+		// Picked up by `resize()` when the window is restored.
+		if self.minimized {
+			return Ok(());
 		}
+
+		wait_frames(&mut self.vw.connection, &mut self.frames)?;
+		self.swapchain(size);
+		self.restyle()
 	}
 
-	pub fn resize(&mut self, size: (u16, u16)) {
+	pub fn resize(&mut self, size: (u16, u16)) -> Result<(), Error> {
+		// A swapchain can't be 0x0, so keep the old one (and don't
+		// draw) until the window is restored.
+		self.minimized = size.0 == 0 || size.1 == 0;
+		if self.minimized {
+			return Ok(());
+		}
+
+		// The old swapchain may still be in use by frames in flight.
+		wait_frames(&mut self.vw.connection, &mut self.frames)?;
+
+		self.swapchain(size);
+
+		// The styles were made for the old render pass.
+		self.restyle()?;
+		self.camera();
+		Ok(())
+	}
+
+	/// Replace the swapchain (or offscreen image) with one that's `size`
+	/// pixels.  Nothing may be using the old one.
+	fn swapchain(&mut self, size: (u16, u16)) {
+		let offscreen = if self.vw.offscreen.is_some() {
+			Some(size)
+		} else {
+			None
+		};
+
 		swapchain_delete(&mut self.vw);
-		let (depth_image, ms_image, render_pass)
-			= swapchain_resize(&self.vw.connection,
+		let (offscreen, depth_image, ms_image, render_pass)
+			= swapchain_resize(&mut self.vw.connection, offscreen,
+				&self.vw.config, &mut self.vw.present_mode,
 				&mut self.vw.image_count,
+				&mut self.vw.samples,
 				&mut self.vw.present_images,
 				&mut self.vw.present_image_views,
 				&mut self.vw.frame_buffers);
 
 		self.ar = size.0 as f32 / size.1 as f32;
+		self.vw.offscreen = offscreen;
 		self.vw.depth_image = depth_image;
 		self.vw.ms_image = ms_image;
 		self.vw.render_pass = render_pass;
-
 		self.projection = ::base::projection(self.ar, 0.5 * PI);
+		// Nothing has been rendered into the new images.
+		self.last_image = None;
+	}
+
+	/// Remake every style for the current render pass, and switch every
+	/// shape over to them.
+	fn restyle(&mut self) -> Result<(), Error> {
+		let styles = Styles::new(&mut self.vw)?;
+		let old = mem::replace(&mut self.styles, styles);
+
+		for (handle, custom) in self.custom_styles.iter_mut_handles() {
+			custom_style(&mut self.vw, handle, custom);
+		}
+
+		for shapes in [&mut self.opaque_vec, &mut self.alpha_vec,
+			&mut self.gui_vec].iter_mut()
+		{
+			for shape in shapes.iter_mut() {
+				let style = shape_style(&self.styles,
+					&self.custom_styles, shape);
+
+				for sprite in shape.instances.iter_mut() {
+					self.vw.connection.sprite_restyle(
+						sprite, style);
+				}
+			}
+		}
+
+		// Nothing uses the old styles anymore.
+		drop(old);
+		Ok(())
+	}
+
+	/// Replace a lost device with a new one, and make everything again on
+	/// it from the CPU copies of models, textures, texcoords, gradients and
+	/// shaders.  The swapchain is made `size` pixels.  If this fails, the
+	/// renderer stays lost (`update()` fails with `DeviceLost`) until it's
+	/// tried again and succeeds.
+	pub fn rebuild(&mut self, size: (u16, u16)) -> Result<(), Error> {
+		self.lost = true;
+
+		// Nothing in flight on the lost device is going to finish, and
+		// everything made on it is freed before it's replaced.
+		let frames = mem::replace(&mut self.frames, Vec::new());
+		let count = frames.len();
+		drop_frames(&mut self.vw.connection, frames);
+		self.unmake();
+
+		// There are always frames to retire garbage into, even if the
+		// reset fails.
+		let reset = self.vw.connection.device_reset();
+		self.frames = new_frames(&mut self.vw.connection, count);
+		self.frame = 0;
+		reset?;
+
+		let remade = self.remake(size);
+		self.lost = remade.is_err();
+		remade
+	}
+
+	/// Free the sprites, samplers, buffers and images made on a lost
+	/// device, for `rebuild()`.  Null ones are left until `remake()`.
+	fn unmake(&mut self) {
+		for shapes in [&mut self.opaque_vec, &mut self.alpha_vec,
+			&mut self.gui_vec].iter_mut()
+		{
+			for shape in shapes.iter_mut() {
+				for sprite in shape.instances.drain(..) {
+					self.vw.connection.sprite_drop(sprite);
+				}
+			}
+		}
+		for (_, sampler) in self.samplers.drain() {
+			self.vw.connection.sampler_drop(sampler);
+		}
+
+		for model in self.models.iter_mut() {
+			let old = mem::replace(&mut model.shape,
+				Default::default());
+			self.vw.connection.buffer_drop(old);
+		}
+		for texcoords in self.texcoords.iter_mut() {
+			let old = mem::replace(&mut texcoords.vertex_buffer,
+				Default::default());
+			self.vw.connection.buffer_drop(old);
+		}
+		for gradient in self.gradients.iter_mut() {
+			let old = mem::replace(&mut gradient.vertex_buffer,
+				Default::default());
+			self.vw.connection.buffer_drop(old);
+		}
+		for texture in self.textures.iter_mut() {
+			if let Some(image) = texture.mappable_image.take() {
+				self.vw.connection.image_drop(image);
+			}
+			if let Some(image) = texture.image.take() {
+				self.vw.connection.image_drop(image);
+			}
+		}
+	}
+
+	/// Make everything again on a new device, for `rebuild()`.
+	fn remake(&mut self, size: (u16, u16)) -> Result<(), Error> {
+		let pipeline_cache = new_pipeline_cache(&mut self.vw.connection,
+			&self.vw.config);
+		let old = mem::replace(&mut self.vw.pipeline_cache,
+			pipeline_cache);
+		self.vw.connection.pipeline_cache_drop(old);
+
+		self.swapchain(size);
+
+		let (r, g, b) = self.clear_color;
+		self.cameras = new_cameras(&mut self.vw.connection,
+			self.clear_color, self.fog);
+		self.vw.connection.color(vec3!(r, g, b));
 		self.camera();
+
+		self.styles = Styles::new(&mut self.vw)?;
+		for (handle, custom) in self.custom_styles.iter_mut_handles() {
+			custom.vert = self.vw.connection.shader(
+				&custom.vert_spirv);
+			custom.frag = self.vw.connection.shader(
+				&custom.frag_spirv);
+			custom_style(&mut self.vw, handle, custom);
+		}
+
+		// The old buffers and images were freed by `unmake()`.
+		for (handle, model) in self.models.iter_mut_handles() {
+			model.shape = self.vw.connection.buffer(
+				&model.vertices)?;
+			name_model(&mut self.vw, handle, model);
+		}
+		for texcoords in self.texcoords.iter_mut() {
+			texcoords.vertex_buffer = self.vw.connection.buffer(
+				&texcoords.data)?;
+		}
+		for gradient in self.gradients.iter_mut() {
+			gradient.vertex_buffer = self.vw.connection.buffer(
+				&gradient.data)?;
+		}
+		for (handle, texture) in self.textures.iter_mut_handles() {
+			let mut new = new_texture(&mut self.vw, texture.w,
+				texture.h, texture.config)?;
+			set_texture(&mut self.vw, &mut new, &texture.rgba)?;
+			name_texture(&mut self.vw, handle, &new);
+
+			*texture = new;
+		}
+
+		for shapes in [&mut self.opaque_vec, &mut self.alpha_vec,
+			&mut self.gui_vec].iter_mut()
+		{
+			for shape in shapes.iter_mut() {
+				let sources = shape.sources;
+				let mut n = 0;
+				let mut buffer = |buffer| {
+					shape.buffers[n] = buffer;
+					n += 1;
+				};
+
+				let model = self.models.get(sources.model)?;
+				buffer(self.vw.connection.vertex_buffer(
+					&model.shape));
+				if let Some(texcoords) = sources.texcoords {
+					buffer(self.vw.connection.vertex_buffer(
+						&self.texcoords.get(texcoords)?
+							.vertex_buffer));
+				}
+				if let Some(gradient) = sources.gradient {
+					buffer(self.vw.connection.vertex_buffer(
+						&self.gradients.get(gradient)?
+							.vertex_buffer));
+				}
+			}
+		}
+
+		self.resprite();
+		Ok(())
 	}
 
-	pub fn texture(&mut self, width: u16, height: u16, rgba: &[u8])
-		-> usize
+	/// Make every shape's sprites again, one for each frame in flight, for
+	/// the current styles, textures and cameras.  The old ones are freed
+	/// once no frame is using them.
+	fn resprite(&mut self) {
+		let every = self.every_frame();
+		let cameras = &self.cameras[..self.frames.len()];
+		let mut old = Vec::new();
+
+		for shapes in [&mut self.opaque_vec, &mut self.alpha_vec,
+			&mut self.gui_vec].iter_mut()
+		{
+			for shape in shapes.iter_mut() {
+				let texture = match shape.sources.texture {
+					Some(texture) => {
+						self.textures.get(texture).ok()
+					}
+					None => None,
+				};
+				let texture = match texture {
+					Some(texture) => Some(bind_texture(
+						&mut self.vw.connection,
+						&mut self.samplers, texture)),
+					None => None,
+				};
+
+				let instances = sprites(&mut self.vw.connection,
+					&*shape.uniform, shape_style(
+						&self.styles,
+						&self.custom_styles, shape),
+					cameras, texture);
+				old.extend(mem::replace(&mut shape.instances,
+					instances));
+				// It may have moved since it was made.
+				shape.stale = every;
+			}
+		}
+
+		for sprite in old {
+			self.retire(Garbage::Sprite(sprite));
+		}
+	}
+
+	/// Copy the last rendered image, which is `wh` pixels, into an RGBA
+	/// buffer.  Returns `None` if nothing has been rendered since the
+	/// swapchain was made.
+	pub fn screenshot(&mut self, wh: (u16, u16))
+		-> Result<Option<Vec<u8>>, Error>
 	{
-		let mut texture = new_texture(&mut self.vw, width, height);
+		let index = match self.last_image {
+			Some(index) => index as usize,
+			None => return Ok(None),
+		};
+		let (width, height) = (wh.0 as usize, wh.1 as usize);
 
-		set_texture(&mut self.vw, &mut texture, rgba);
+		// Make sure the last frame has finished rendering.
+		wait_frames(&mut self.vw.connection, &mut self.frames)?;
+
+		let image = self.vw.connection.image(
+			width as u32, height as u32, 1,
+			VkFormat::R8g8b8a8Unorm,
+			VkImageTiling::Linear,
+			VkImageUsage::TransferDstBit,
+			VkImageLayout::Undefined,
+			0x00000006 /* visible|coherent */,
+			VkSampleCount::Sc1
+		)?;
+		let pitch = self.vw.connection.image_pitch(&image);
+
+		self.vw.connection.image_read_back(self.vw.present_images[index],
+			&image, wh.0, wh.1);
+
+		let mut rgba = vec![0; width * height * 4];
+		let read = self.vw.connection.image_read(&image, &mut rgba,
+			width, height, pitch as usize);
+		self.vw.connection.image_drop(image);
+		read?;
+
+		// Swapchains are often BGRA.
+		match self.vw.connection.present_format() {
+			VkFormat::B8g8r8a8Unorm | VkFormat::B8g8r8a8Srgb => {
+				for pixel in rgba.chunks_mut(4) {
+					pixel.swap(0, 2);
+				}
+			}
+			_ => {}
+		}
 
-		let a = self.textures.len();
-		self.textures.push(texture);
-		a
+		Ok(Some(rgba))
 	}
 
-	pub fn set_texture(&mut self, texture: usize, rgba: &[u8]) {
-		set_texture(&mut self.vw, &mut self.textures[texture], rgba);
+	pub fn texture(&mut self, width: u16, height: u16, rgba: &[u8],
+		config: TextureConfig) -> Result<usize, Error>
+	{
+		let mut texture = new_texture(&mut self.vw, width, height,
+			config)?;
+
+		if let Err(e) = set_texture(&mut self.vw, &mut texture, rgba) {
+			self.retire_texture(texture);
+			return Err(e);
+		}
+
+		let handle = match self.textures.insert(texture) {
+			Ok(handle) => handle,
+			Err(texture) => {
+				self.retire_texture(texture);
+				return Err(Error::OutOfHandles);
+			}
+		};
+		name_texture(&mut self.vw, handle, self.textures.get(handle)?);
+		Ok(handle as usize)
+	}
+
+	pub fn set_texture(&mut self, texture: usize, rgba: &[u8])
+		-> Result<(), Error>
+	{
+		self.textures.index(key(texture))?;
+		self.finish_texture(key(texture))?;
+
+		let texture = self.textures.get_mut(key(texture))?;
+
+		set_texture(&mut self.vw, texture, rgba)
+	}
+
+	/// Replace the `width` by `height` rectangle of a texture with its top
+	/// left corner at `(x, y)`, uploading only that rectangle.
+	pub fn set_texture_region(&mut self, texture: usize, x: u16, y: u16,
+		width: u16, height: u16, rgba: &[u8]) -> Result<(), Error>
+	{
+		self.textures.index(key(texture))?;
+
+		// Nothing to write.
+		if width == 0 || height == 0 {
+			return Ok(());
+		}
+
+		self.finish_texture(key(texture))?;
+
+		let texture = self.textures.get_mut(key(texture))?;
+
+		if x as u32 + width as u32 > texture.w as u32
+			|| y as u32 + height as u32 > texture.h as u32
+			|| rgba.len() != width as usize * height as usize
+				* texture.config.format.bytes_per_pixel()
+		{
+			return Err(Error::InvalidRegion);
+		}
+
+		set_texture_region(&mut self.vw, texture, x, y, width, height,
+			rgba)
 	}
 
 	pub fn resize_texture(&mut self, texture_id: usize, width: u16,
-		height: u16, rgba: &[u8])
+		height: u16, rgba: &[u8]) -> Result<(), Error>
 	{
-		println!("RESIZE TX");
-		let mut texture = new_texture(&mut self.vw, width, height);
-		set_texture(&mut self.vw, &mut texture, rgba);
-		self.textures[texture_id] = texture;
+		let config = self.textures.get(key(texture_id))?.config;
+
+		let mut texture = new_texture(&mut self.vw, width, height,
+			config)?;
+		if let Err(e) = set_texture(&mut self.vw, &mut texture, rgba) {
+			self.retire_texture(texture);
+			return Err(e);
+		}
+		name_texture(&mut self.vw, key(texture_id), &texture);
+		let old = mem::replace(self.textures.get_mut(key(texture_id))?,
+			texture);
+		self.retire_texture(old);
+		Ok(())
 	}
 
 	/// Push a model (collection of vertices) into graphics memory.
 	pub fn model(&mut self, vertices: &[f32], fans: Vec<(u32, u32)>)
-		-> usize
+		-> Result<usize, Error>
 	{
-		let shape = unsafe {
-			asi_vulkan::new_buffer(&self.vw.connection,
-				vertices)
-		};
-
-		let a = self.models.len();
-
-		self.models.push(Model {
+		let shape = self.vw.connection.buffer(vertices)?;
+		let handle = match self.models.insert(Model {
 			shape,
 			vertex_count: vertices.len() as u32 / 4,
 			fans,
-		});
+			vertices: vertices.to_vec(),
+		}) {
+			Ok(handle) => handle,
+			Err(model) => {
+				self.retire(Garbage::Buffer(model.shape));
+				return Err(Error::OutOfHandles);
+			}
+		};
 
-		a
+		name_model(&mut self.vw, handle, self.models.get(handle)?);
+		Ok(handle as usize)
 	}
 
 	/// Push texture coordinates (collection of vertices) into graphics
 	/// memory.
-	pub fn texcoords(&mut self, texcoords: &[f32]) -> usize {
-		let vertex_buffer = unsafe {
-			asi_vulkan::new_buffer(
-				&self.vw.connection,
-				texcoords,
-			)
-		};
-
-		let a = self.texcoords.len();
+	pub fn texcoords(&mut self, texcoords: &[f32])
+		-> Result<usize, Error>
+	{
+		let vertex_buffer = self.vw.connection.buffer(texcoords)?;
 
-		self.texcoords.push(TexCoords {
+		match self.texcoords.insert(TexCoords {
 			vertex_buffer,
 			vertex_count: texcoords.len() as u32 / 4,
-		});
-
-		a
+			data: texcoords.to_vec(),
+		}) {
+			Ok(handle) => Ok(handle as usize),
+			Err(texcoords) => {
+				self.retire(Garbage::Buffer(
+					texcoords.vertex_buffer));
+				Err(Error::OutOfHandles)
+			}
+		}
 	}
 
 	/// Push colors per vertex into graphics memory.
-	pub fn colors(&mut self, colors: &[f32]) -> usize {
-		let vertex_buffer = unsafe {
-			asi_vulkan::new_buffer(
-				&self.vw.connection,
-				colors,
-			)
-		};
-
-		let a = self.gradients.len();
+	pub fn colors(&mut self, colors: &[f32]) -> Result<usize, Error> {
+		let vertex_buffer = self.vw.connection.buffer(colors)?;
 
-		self.gradients.push(Gradient {
+		match self.gradients.insert(Gradient {
 			vertex_buffer,
 			vertex_count: colors.len() as u32 / 4,
-		});
+			data: colors.to_vec(),
+		}) {
+			Ok(handle) => Ok(handle as usize),
+			Err(gradient) => {
+				self.retire(Garbage::Buffer(
+					gradient.vertex_buffer));
+				Err(Error::OutOfHandles)
+			}
+		}
+	}
+
+	/// Free a model's GPU memory once no frame in flight uses it.  Fails
+	/// if shapes made from the model haven't been dropped.
+	pub fn drop_model(&mut self, model: usize) -> Result<(), HandleError> {
+		self.models.index(key(model))?;
+		self.unused(key(model))?;
+
+		let model = self.models.remove(key(model))?;
+
+		self.retire(Garbage::Buffer(model.shape));
+		Ok(())
+	}
+
+	/// Free a texture's GPU memory once no frame in flight uses it.  Fails
+	/// if shapes made from the texture haven't been dropped.
+	pub fn drop_texture(&mut self, texture: usize)
+		-> Result<(), HandleError>
+	{
+		self.textures.index(key(texture))?;
+		self.unused(key(texture))?;
+
+		let texture = self.textures.remove(key(texture))?;
+
+		self.retire_texture(texture);
+		Ok(())
+	}
+
+	/// Free texture coordinates' GPU memory once no frame in flight uses
+	/// them.  Fails if shapes made from them haven't been dropped.
+	pub fn drop_texcoords(&mut self, texcoords: usize)
+		-> Result<(), HandleError>
+	{
+		self.texcoords.index(key(texcoords))?;
+		self.unused(key(texcoords))?;
 
-		a
+		let texcoords = self.texcoords.remove(key(texcoords))?;
+
+		self.retire(Garbage::Buffer(texcoords.vertex_buffer));
+		Ok(())
+	}
+
+	/// Free a gradient's GPU memory once no frame in flight uses it.
+	/// Fails if shapes made from the gradient haven't been dropped.
+	pub fn drop_gradient(&mut self, gradient: usize)
+		-> Result<(), HandleError>
+	{
+		self.gradients.index(key(gradient))?;
+		self.unused(key(gradient))?;
+
+		let gradient = self.gradients.remove(key(gradient))?;
+
+		self.retire(Garbage::Buffer(gradient.vertex_buffer));
+		Ok(())
+	}
+
+	/// Fail if any shape was made from the object `handle` refers to.
+	fn unused(&self, handle: u32) -> Result<(), HandleError> {
+		if self.users.contains_key(&handle) {
+			Err(HandleError::InUse)
+		} else {
+			Ok(())
+		}
+	}
+
+	fn retire_texture(&mut self, texture: Texture<D>) {
+		if let Some(image) = texture.mappable_image {
+			self.retire(Garbage::Image(image));
+		}
+		if let Some(image) = texture.image {
+			self.retire(Garbage::Image(image));
+		}
+	}
+
+	/// Free `garbage` after the last submitted frame finishes, or right
+	/// away if no frame is in flight.  Frames finish in order, so that's
+	/// after every frame that could be using it.
+	fn retire(&mut self, garbage: Garbage<D>) {
+		let last = (self.frame + self.frames.len() - 1)
+			% self.frames.len();
+
+		if self.frames[last].submitted {
+			self.frames[last].garbage.push(garbage);
+		} else {
+			free_garbage(&mut self.vw.connection, vec![garbage]);
+		}
+	}
+
+	/// Add a shape to the GUI, alpha or opaque shapes (see
+	/// `shape_kind()`).
+	fn insert_shape(&mut self, shape: Shape<D>, alpha: bool, fog: bool,
+		camera: bool) -> Result<ShapeHandle, Error>
+	{
+		let handle = shape_kind(alpha, fog, camera);
+		let (shapes, ind) = match handle(0) {
+			ShapeHandle::Gui(_) => {
+				(&mut self.gui_vec, &mut self.gui_ind)
+			}
+			ShapeHandle::Alpha(_) => {
+				(&mut self.alpha_vec, &mut self.alpha_ind)
+			}
+			ShapeHandle::Opaque(_) => {
+				(&mut self.opaque_vec, &mut self.opaque_ind)
+			}
+		};
+
+		// Draw after other shapes on the same layer.
+		let layer = shape.layer;
+		let sources = shape.sources;
+		let key = match shapes.insert(shape) {
+			Ok(key) => key,
+			Err(shape) => {
+				for sprite in shape.instances {
+					self.retire(Garbage::Sprite(sprite));
+				}
+				return Err(Error::OutOfHandles);
+			}
+		};
+		let at = ind.iter().position(|i| shapes.at(*i as usize).layer
+			> layer).unwrap_or(ind.len());
+		ind.insert(at, shapes.index(key).unwrap() as u32);
+
+		for handle in sources.handles() {
+			*self.users.entry(handle).or_insert(0) += 1;
+		}
+
+		Ok(handle(key))
+	}
+
+	/// Set the layer of a GUI shape.  Shapes on higher layers are drawn on
+	/// top, and shapes on the same layer are drawn in the order they were
+	/// made.
+	pub fn gui_layer(&mut self, shape: &ShapeHandle, layer: i32)
+		-> Result<(), HandleError>
+	{
+		let index = match *shape {
+			ShapeHandle::Gui(x) => self.gui_vec.index(x)?,
+			_ => return Err(HandleError::Foreign),
+		};
+
+		self.gui_vec.get_mut_at(index).layer = layer;
+
+		// Stable, so the order within each layer is kept.
+		let gui_vec = &self.gui_vec;
+		self.gui_ind.sort_by_key(|i| gui_vec.at(*i as usize).layer);
+		Ok(())
 	}
 
 	pub fn textured(&mut self, model: usize, mat4: Transform,
 		texture: usize, texcoords: usize, alpha: bool,
-		fog: bool, camera: bool) -> ShapeHandle
+		fog: bool, camera: bool) -> Result<ShapeHandle, Error>
 	{
-		if self.models[model].vertex_count
-			!= self.texcoords[texcoords].vertex_count
-		{
-			panic!("TexCoord length doesn't match vertex length");
+		let sources = Sources {
+			model: key(model),
+			texture: Some(key(texture)),
+			texcoords: Some(key(texcoords)),
+			gradient: None,
+		};
+		let model = self.models.get(key(model))?;
+		let texture = self.textures.get(key(texture))?;
+		let texcoords = self.texcoords.get(key(texcoords))?;
+
+		if model.vertex_count != texcoords.vertex_count {
+			return Err(Error::MismatchedVertexCount);
 		}
 
 		// Add an instance
-		let instance = unsafe {
-			Sprite::new(
-				&self.vw.connection,
+		let uniform = Box::new(TransformFullUniform {
+			mat4: mat4.into(),
+			hcam: fog as u32 + camera as u32,
+		});
+		let texture = bind_texture(&mut self.vw.connection,
+			&mut self.samplers, texture);
+		let instances = sprites(&mut self.vw.connection, &*uniform,
 				if alpha {
-					&self.style_texture
+					&self.styles.texture
 				} else {
-					&self.style_natexture
+					&self.styles.natexture
 				},
-				TransformFullUniform {
-					mat4: mat4.into(),
-					hcam: fog as u32 + camera as u32,
-				},
-				&self.camera_memory, // TODO: at shader creation, not shape creation
-				Some(&self.effect_memory),
-				Some(self.textures[texture].image.as_ref()
-					.unwrap_or(&self.textures[texture]
-						.mappable_image).clone()),
-				true, // 1 texure
-			)
-		};
+				&self.cameras[..self.frames.len()],
+				Some(texture),
+			);
 
 		let shape = Shape {
-			instance,
+			instances,
+			stale: 0,
 			num_buffers: 2,
 			buffers: [
-				self.models[model].shape.buffer(),
-				self.texcoords[texcoords].vertex_buffer.buffer(),
-				unsafe { mem::uninitialized() }
+				self.vw.connection.vertex_buffer(&model.shape),
+				self.vw.connection.vertex_buffer(
+					&texcoords.vertex_buffer),
+				D::VertexBuffer::default()
 			],
-			fans: self.models[model].fans.clone(),
+			fans: model.fans.clone(),
 			transform: mat4,
+			layer: 0,
+			style: StyleId::Texture,
+			blending: alpha,
+			hcam: fog as u32 + camera as u32,
+			sources,
+			uniform,
 		};
 
-		if !camera && !fog {
-			self.gui_vec.push(shape);
-			ShapeHandle::Gui(self.gui_vec.len() as u32 - 1)
-		} else if alpha {
-			let index = self.alpha_vec.len() as u32;
-			self.alpha_vec.push(shape);
-			self.alpha_ind.push(index);
-			ShapeHandle::Alpha(index)
-		} else {
-			let index = self.opaque_vec.len() as u32;
-			self.opaque_vec.push(shape);
-			self.opaque_ind.push(index);
-			ShapeHandle::Opaque(index)
-		}
+		self.insert_shape(shape, alpha, fog, camera)
 	}
 
 	pub fn solid(&mut self, model: usize, mat4: Transform, color: [f32; 4],
 		alpha: bool, fog: bool, camera: bool)
-		-> ShapeHandle
+		-> Result<ShapeHandle, Error>
 	{
+		let sources = Sources {
+			model: key(model),
+			texture: None,
+			texcoords: None,
+			gradient: None,
+		};
+		let model = self.models.get(key(model))?;
+
 		// Add an instance
-		let instance = unsafe {
-			Sprite::new(
-				&self.vw.connection,
+		let uniform = Box::new(TransformAndColorUniform {
+			vec4: color,
+			hcam: fog as u32 + camera as u32,
+			mat4: mat4.into(),
+		});
+		let instances = sprites(&mut self.vw.connection, &*uniform,
 				if alpha {
-					&self.style_solid
+					&self.styles.solid
 				} else {
-					&self.style_nasolid
-				},
-				TransformAndColorUniform {
-					vec4: color,
-					hcam: fog as u32 + camera as u32,
-					mat4: mat4.into(),
+					&self.styles.nasolid
 				},
-				&self.camera_memory,
-				Some(&self.effect_memory),
+				&self.cameras[..self.frames.len()],
 				None,
-				false, // no texure
-			)
-		};
+			);
 
 		let shape = Shape {
-			instance,
+			instances,
+			stale: 0,
 			num_buffers: 1,
 			buffers: [
-				self.models[model].shape.buffer(),
-				unsafe { mem::uninitialized() },
-				unsafe { mem::uninitialized() }
+				self.vw.connection.vertex_buffer(&model.shape),
+				D::VertexBuffer::default(),
+				D::VertexBuffer::default()
 			],
-			fans: self.models[model].fans.clone(),
+			fans: model.fans.clone(),
 			transform: mat4,
+			layer: 0,
+			style: StyleId::Solid,
+			blending: alpha,
+			hcam: fog as u32 + camera as u32,
+			sources,
+			uniform,
 		};
 
-		if !camera && !fog {
-			self.gui_vec.push(shape);
-			ShapeHandle::Gui(self.gui_vec.len() as u32 - 1)
-		} else if alpha {
-			let index = self.alpha_vec.len() as u32;
-			self.alpha_vec.push(shape);
-			self.alpha_ind.push(index);
-			ShapeHandle::Alpha(index)
-		} else {
-			let index = self.opaque_vec.len() as u32;
-			self.opaque_vec.push(shape);
-			self.opaque_ind.push(index);
-			ShapeHandle::Opaque(index)
-		}
+		self.insert_shape(shape, alpha, fog, camera)
 	}
 
 	pub fn gradient(&mut self, model: usize, mat4: Transform, colors: usize,
 		alpha: bool, fog: bool, camera: bool)
-		-> ShapeHandle
+		-> Result<ShapeHandle, Error>
 	{
-		if self.models[model].vertex_count
-			!= self.gradients[colors].vertex_count
-		{
-			panic!("TexCoord length doesn't match gradient length");
+		let sources = Sources {
+			model: key(model),
+			texture: None,
+			texcoords: None,
+			gradient: Some(key(colors)),
+		};
+		let model = self.models.get(key(model))?;
+		let colors = self.gradients.get(key(colors))?;
+
+		if model.vertex_count != colors.vertex_count {
+			return Err(Error::MismatchedVertexCount);
 		}
 
 		// Add an instance
-		let instance = unsafe {
-			Sprite::new(
-				&self.vw.connection,
+		let uniform = Box::new(TransformFullUniform {
+			mat4: mat4.into(),
+			hcam: fog as u32 + camera as u32,
+		});
+		let instances = sprites(&mut self.vw.connection, &*uniform,
 				if alpha {
-					&self.style_gradient
+					&self.styles.gradient
 				} else {
-					&self.style_nagradient
-				},
-				TransformFullUniform {
-					mat4: mat4.into(),
-					hcam: fog as u32 + camera as u32,
+					&self.styles.nagradient
 				},
-				&self.camera_memory,
-				Some(&self.effect_memory),
+				&self.cameras[..self.frames.len()],
 				None,
-				false, // no texure
-			)
-		};
+			);
 
 		let shape = Shape {
-			instance,
+			instances,
+			stale: 0,
 			num_buffers: 2,
 			buffers: [
-				self.models[model].shape.buffer(),
-				self.gradients[colors].vertex_buffer.buffer(),
-				unsafe { mem::uninitialized() }
+				self.vw.connection.vertex_buffer(&model.shape),
+				self.vw.connection.vertex_buffer(
+					&colors.vertex_buffer),
+				D::VertexBuffer::default()
 			],
-			fans: self.models[model].fans.clone(),
+			fans: model.fans.clone(),
 			transform: mat4,
+			layer: 0,
+			style: StyleId::Gradient,
+			blending: alpha,
+			hcam: fog as u32 + camera as u32,
+			sources,
+			uniform,
 		};
 
-		if !camera && !fog {
-			self.gui_vec.push(shape);
-			ShapeHandle::Gui(self.gui_vec.len() as u32 - 1)
-		} else if alpha {
-			let index = self.alpha_vec.len() as u32;
-			self.alpha_vec.push(shape);
-			self.alpha_ind.push(index);
-			ShapeHandle::Alpha(index)
-		} else {
-			let index = self.opaque_vec.len() as u32;
-			self.opaque_vec.push(shape);
-			self.opaque_ind.push(index);
-			ShapeHandle::Opaque(index)
-		}
+		self.insert_shape(shape, alpha, fog, camera)
 	}
 
 	pub fn faded(&mut self, model: usize, mat4: Transform, texture: usize,
 		texcoords: usize, fade_factor: f32, fog: bool,
-		camera: bool) -> ShapeHandle
+		camera: bool) -> Result<ShapeHandle, Error>
 	{
-		if self.models[model].vertex_count
-			!= self.texcoords[texcoords].vertex_count
-		{
-			panic!("TexCoord length doesn't match vertex length");
+		let sources = Sources {
+			model: key(model),
+			texture: Some(key(texture)),
+			texcoords: Some(key(texcoords)),
+			gradient: None,
+		};
+		let model = self.models.get(key(model))?;
+		let texture = self.textures.get(key(texture))?;
+		let texcoords = self.texcoords.get(key(texcoords))?;
+
+		if model.vertex_count != texcoords.vertex_count {
+			return Err(Error::MismatchedVertexCount);
 		}
 
 		// Add an instance
-		let instance = unsafe {
-			Sprite::new(
-				&self.vw.connection,
-				&self.style_faded,
-				TransformAndFadeUniform {
-					mat4: mat4.into(),
-					hcam: fog as u32 + camera as u32,
-					fade: fade_factor,
-				},
-				&self.camera_memory,
-				Some(&self.effect_memory),
-				Some(self.textures[texture].image.as_ref()
-					.unwrap_or(&self.textures[texture]
-						.mappable_image).clone()),
-				true, // 1 texure
-			)
-		};
+		let uniform = Box::new(TransformAndFadeUniform {
+			mat4: mat4.into(),
+			hcam: fog as u32 + camera as u32,
+			fade: fade_factor,
+		});
+		let texture = bind_texture(&mut self.vw.connection,
+			&mut self.samplers, texture);
+		let instances = sprites(&mut self.vw.connection, &*uniform,
+				&self.styles.faded,
+				&self.cameras[..self.frames.len()],
+				Some(texture),
+			);
 
 		let shape = Shape {
-			instance,
+			instances,
+			stale: 0,
 			num_buffers: 2,
 			buffers: [
-				self.models[model].shape.buffer(),
-				self.texcoords[texcoords].vertex_buffer.buffer(),
-				unsafe { mem::uninitialized() }
+				self.vw.connection.vertex_buffer(&model.shape),
+				self.vw.connection.vertex_buffer(
+					&texcoords.vertex_buffer),
+				D::VertexBuffer::default()
 			],
-			fans: self.models[model].fans.clone(),
+			fans: model.fans.clone(),
 			transform: mat4,
+			layer: 0,
+			style: StyleId::Faded,
+			blending: true,
+			hcam: fog as u32 + camera as u32,
+			sources,
+			uniform,
 		};
 
-		if !camera && !fog {
-			self.gui_vec.push(shape);
-			ShapeHandle::Gui(self.gui_vec.len() as u32 - 1)
-		} else {
-			let index = self.alpha_vec.len() as u32;
-			self.alpha_vec.push(shape);
-			self.alpha_ind.push(index);
-			ShapeHandle::Alpha(index)
-		}
+		// Faded shapes always blend.
+		self.insert_shape(shape, true, fog, camera)
 	}
 
 	pub fn tinted(&mut self, model: usize, mat4: Transform,
 		texture: usize, texcoords: usize, color: [f32; 4],
 		alpha: bool, fog: bool, camera: bool)
-		-> ShapeHandle
+		-> Result<ShapeHandle, Error>
 	{
-		if self.models[model].vertex_count
-			!= self.texcoords[texcoords].vertex_count
-		{
-			panic!("TexCoord length doesn't match vertex length");
+		let sources = Sources {
+			model: key(model),
+			texture: Some(key(texture)),
+			texcoords: Some(key(texcoords)),
+			gradient: None,
+		};
+		let model = self.models.get(key(model))?;
+		let texture = self.textures.get(key(texture))?;
+		let texcoords = self.texcoords.get(key(texcoords))?;
+
+		if model.vertex_count != texcoords.vertex_count {
+			return Err(Error::MismatchedVertexCount);
 		}
 
 		// Add an instance
-		let instance = unsafe {
-			Sprite::new(
-				&self.vw.connection,
+		let uniform = Box::new(TransformAndColorUniform {
+			mat4: mat4.into(),
+			hcam: fog as u32 + camera as u32,
+			vec4: color,
+		});
+		let texture = bind_texture(&mut self.vw.connection,
+			&mut self.samplers, texture);
+		let instances = sprites(&mut self.vw.connection, &*uniform,
 				if alpha {
-					&self.style_tinted
+					&self.styles.tinted
 				} else {
-					&self.style_natinted
-				},
-				TransformAndColorUniform {
-					mat4: mat4.into(),
-					hcam: fog as u32 + camera as u32,
-					vec4: color,
+					&self.styles.natinted
 				},
-				&self.camera_memory,
-				Some(&self.effect_memory),
-				Some(self.textures[texture].image.as_ref()
-					.unwrap_or(&self.textures[texture]
-						.mappable_image).clone()),
-				true, // 1 texure
-			)
-		};
+				&self.cameras[..self.frames.len()],
+				Some(texture),
+			);
 
 		let shape = Shape {
-			instance,
+			instances,
+			stale: 0,
 			num_buffers: 2,
 			buffers: [
-				self.models[model].shape.buffer(),
-				self.texcoords[texcoords].vertex_buffer.buffer(),
-				unsafe { mem::uninitialized() }
+				self.vw.connection.vertex_buffer(&model.shape),
+				self.vw.connection.vertex_buffer(
+					&texcoords.vertex_buffer),
+				D::VertexBuffer::default()
 			],
-			fans: self.models[model].fans.clone(),
+			fans: model.fans.clone(),
 			transform: mat4,
+			layer: 0,
+			style: StyleId::Tinted,
+			blending: alpha,
+			hcam: fog as u32 + camera as u32,
+			sources,
+			uniform,
 		};
 
-		if !camera && !fog {
-			self.gui_vec.push(shape);
-			ShapeHandle::Gui(self.gui_vec.len() as u32 - 1)
-		} else if alpha {
-			let index = self.alpha_vec.len() as u32;
-			self.alpha_vec.push(shape);
-			self.alpha_ind.push(index);
-			ShapeHandle::Alpha(index)
-		} else {
-			let index = self.opaque_vec.len() as u32;
-			self.opaque_vec.push(shape);
-			self.opaque_ind.push(index);
-			ShapeHandle::Opaque(index)
-		}
+		self.insert_shape(shape, alpha, fog, camera)
 	}
 
 	pub fn complex(&mut self, model: usize, mat4: Transform,
 		texture: usize, texcoords: usize, colors: usize, alpha: bool,
-		fog: bool, camera: bool) -> ShapeHandle
+		fog: bool, camera: bool) -> Result<ShapeHandle, Error>
 	{
-		if self.models[model].vertex_count
-			!= self.texcoords[texcoords].vertex_count ||
-			self.models[model].vertex_count
-			!= self.gradients[colors].vertex_count
+		let sources = Sources {
+			model: key(model),
+			texture: Some(key(texture)),
+			texcoords: Some(key(texcoords)),
+			gradient: Some(key(colors)),
+		};
+		let model = self.models.get(key(model))?;
+		let texture = self.textures.get(key(texture))?;
+		let texcoords = self.texcoords.get(key(texcoords))?;
+		let colors = self.gradients.get(key(colors))?;
+
+		if model.vertex_count != texcoords.vertex_count ||
+			model.vertex_count != colors.vertex_count
 		{
-			panic!("TexCoord length doesn't match vertex length");
+			return Err(Error::MismatchedVertexCount);
 		}
 
 		// Add an instance
-		let instance = unsafe {
-			Sprite::new(
-				&self.vw.connection,
+		let uniform = Box::new(TransformFullUniform {
+			mat4: mat4.into(),
+			hcam: fog as u32 + camera as u32,
+		});
+		let texture = bind_texture(&mut self.vw.connection,
+			&mut self.samplers, texture);
+		let instances = sprites(&mut self.vw.connection, &*uniform,
 				if alpha {
-					&self.style_complex
+					&self.styles.complex
 				} else {
-					&self.style_nacomplex
+					&self.styles.nacomplex
 				},
-				TransformFullUniform {
-					mat4: mat4.into(),
-					hcam: fog as u32 + camera as u32,
-				},
-				&self.camera_memory,
-				Some(&self.effect_memory),
-				Some(self.textures[texture].image.as_ref()
-					.unwrap_or(&self.textures[texture]
-						.mappable_image).clone()),
-				true, // 1 texure
-			)
-		};
+				&self.cameras[..self.frames.len()],
+				Some(texture),
+			);
 
 		let shape = Shape {
-			instance,
+			instances,
+			stale: 0,
 			num_buffers: 3,
 			buffers: [
-				self.models[model].shape.buffer(),
-				self.texcoords[texcoords].vertex_buffer.buffer(),
-				self.gradients[colors].vertex_buffer.buffer(),
+				self.vw.connection.vertex_buffer(&model.shape),
+				self.vw.connection.vertex_buffer(
+					&texcoords.vertex_buffer),
+				self.vw.connection.vertex_buffer(
+					&colors.vertex_buffer),
 			],
-			fans: self.models[model].fans.clone(),
+			fans: model.fans.clone(),
 			transform: mat4,
+			layer: 0,
+			style: StyleId::Complex,
+			blending: alpha,
+			hcam: fog as u32 + camera as u32,
+			sources,
+			uniform,
 		};
 
-		if !camera && !fog {
-			self.gui_vec.push(shape);
-			ShapeHandle::Gui(self.gui_vec.len() as u32 - 1)
-		} else if alpha {
-			let index = self.alpha_vec.len() as u32;
-			self.alpha_vec.push(shape);
-			self.alpha_ind.push(index);
-			ShapeHandle::Alpha(index)
-		} else {
-			let index = self.opaque_vec.len() as u32;
-			self.opaque_vec.push(shape);
-			self.opaque_ind.push(index);
-			ShapeHandle::Opaque(index)
+		self.insert_shape(shape, alpha, fog, camera)
+	}
+
+	/// Make a style from the user's shaders, whose part of the uniform is
+	/// `U`.
+	pub fn style<U>(&mut self, vert: &[u8], frag: &[u8], textures: u32,
+		vertex_buffers: u32) -> Result<u32, Error>
+	{
+		if textures > 1 {
+			return Err(Error::InvalidShader("Styles can't have \
+				more than 1 texture".to_string()));
 		}
+		if vertex_buffers < 1 || vertex_buffers > 3 {
+			return Err(Error::InvalidShader("Styles must have 1 \
+				to 3 vertex buffers".to_string()));
+		}
+
+		spirv::check("Custom", vert, frag,
+			mem::size_of::<CustomUniform<U>>(), textures,
+			vertex_buffers).map_err(Error::InvalidShader)?;
+
+		let (vert_spirv, frag_spirv) = (vert, frag);
+		let vert = self.vw.connection.shader(vert);
+		let frag = self.vw.connection.shader(frag);
+		let style = self.vw.connection.style(self.vw.render_pass,
+			&self.vw.pipeline_cache, &vert, &frag, textures,
+			vertex_buffers, true, self.vw.samples);
+		let nastyle = self.vw.connection.style(self.vw.render_pass,
+			&self.vw.pipeline_cache, &vert, &frag, textures,
+			vertex_buffers, false, self.vw.samples);
+
+		let handle = self.custom_styles.insert(CustomStyle {
+			vert_spirv: vert_spirv.to_vec(),
+			frag_spirv: frag_spirv.to_vec(),
+			vert, frag, style, nastyle, textures, vertex_buffers
+		}).map_err(|_| Error::OutOfHandles)?;
+
+		name_custom_style(&mut self.vw, handle,
+			self.custom_styles.get(handle)?);
+		Ok(handle)
 	}
 
-	pub fn drop_shape(&mut self, shape: ShapeHandle) {
-		match shape {
-			ShapeHandle::Opaque(x) => {
-				let index = self.opaque_ind.iter()
-					.position(|y| *y == x).unwrap();
-				self.opaque_ind.remove(index);
-			},
-			ShapeHandle::Alpha(x) => {
-				let index = self.alpha_ind.iter()
-					.position(|y| *y == x).unwrap();
-				self.alpha_ind.remove(index);
-			},
-			ShapeHandle::Gui(x) => {
-				// TODO: make it obvious that there's only meant
-				// to be 1 GUI object.
-				self.gui_vec.clear();
-			},
+	/// Make a shape with a custom style.  The vertex buffers are the
+	/// model's, then `texcoords`' and `colors`' if they're `Some`.
+	pub fn custom<U: Clone + 'static>(&mut self, style: u32, model: usize,
+		mat4: Transform, uniform: U, texture: Option<usize>,
+		texcoords: Option<usize>, colors: Option<usize>, alpha: bool,
+		fog: bool, camera: bool) -> Result<ShapeHandle, Error>
+	{
+		let custom = self.custom_styles.get(style)?;
+		let sources = Sources {
+			model: key(model),
+			texture: texture.map(key),
+			texcoords: texcoords.map(key),
+			gradient: colors.map(key),
+		};
+		let model = self.models.get(key(model))?;
+		let mut buffers = [
+			self.vw.connection.vertex_buffer(&model.shape),
+			D::VertexBuffer::default(),
+			D::VertexBuffer::default()
+		];
+		let mut num_buffers = 1;
+
+		if let Some(texcoords) = texcoords {
+			let texcoords = self.texcoords.get(key(texcoords))?;
+
+			if model.vertex_count != texcoords.vertex_count {
+				return Err(Error::MismatchedVertexCount);
+			}
+
+			buffers[num_buffers] = self.vw.connection.vertex_buffer(
+				&texcoords.vertex_buffer);
+			num_buffers += 1;
+		}
+
+		if let Some(colors) = colors {
+			let colors = self.gradients.get(key(colors))?;
+
+			if model.vertex_count != colors.vertex_count {
+				return Err(Error::MismatchedVertexCount);
+			}
+
+			buffers[num_buffers] = self.vw.connection.vertex_buffer(
+				&colors.vertex_buffer);
+			num_buffers += 1;
+		}
+
+		if num_buffers != custom.vertex_buffers as usize {
+			return Err(Error::MismatchedVertexCount);
+		}
+
+		if texture.is_some() as u32 != custom.textures {
+			return Err(Error::MismatchedTextureCount);
 		}
+
+		let texture = match texture {
+			Some(texture) => {
+				let texture = self.textures.get(key(texture))?;
+
+				Some(bind_texture(&mut self.vw.connection,
+					&mut self.samplers, texture))
+			}
+			None => None,
+		};
+
+		let hcam = fog as u32 + camera as u32;
+		let uniform = Box::new(CustomUniform {
+			mat4: mat4.into(),
+			hcam,
+			_pad: [0; 3],
+			data: uniform,
+		});
+		let instances = sprites(&mut self.vw.connection, &*uniform,
+				if alpha {
+					&custom.style
+				} else {
+					&custom.nastyle
+				},
+				&self.cameras[..self.frames.len()],
+				texture,
+			);
+
+		let shape = Shape {
+			instances,
+			stale: 0,
+			num_buffers,
+			buffers,
+			fans: model.fans.clone(),
+			transform: mat4,
+			layer: 0,
+			style: StyleId::Custom(style),
+			blending: alpha,
+			hcam,
+			sources,
+			uniform,
+		};
+
+		self.insert_shape(shape, alpha, fog, camera)
 	}
 
-	pub fn transform(&mut self, shape: &ShapeHandle, transform: Transform) {
-		let uniform = TransformUniform {
-			mat4: transform.into(),
+	/// Change the uniform of a shape made with the custom `style`.
+	pub fn set_uniform<U: Clone + 'static>(&mut self, shape: &ShapeHandle,
+		style: u32, uniform: U) -> Result<(), Error>
+	{
+		// Check that `uniform` is the type the shape was made with.
+		self.custom_styles.index(style)?;
+		if match *shape {
+			ShapeHandle::Opaque(x) => self.opaque_vec.get(x)?,
+			ShapeHandle::Alpha(x) => self.alpha_vec.get(x)?,
+			ShapeHandle::Gui(x) => self.gui_vec.get(x)?,
+		}.style != StyleId::Custom(style) {
+			return Err(HandleError::Foreign.into());
+		}
+
+		let every = self.every_frame();
+		let shape = match *shape {
+			ShapeHandle::Opaque(x) => self.opaque_vec.get_mut(x)?,
+			ShapeHandle::Alpha(x) => self.alpha_vec.get_mut(x)?,
+			ShapeHandle::Gui(x) => self.gui_vec.get_mut(x)?,
 		};
+		let uniform = CustomUniform {
+			mat4: shape.transform.into(),
+			hcam: shape.hcam,
+			_pad: [0; 3],
+			data: uniform,
+		};
+
+		// Written into each frame's sprite once it's not in flight.
+		shape.uniform = Box::new(uniform);
+		shape.stale = every;
+		Ok(())
+	}
 
-		match shape {
+	pub fn drop_shape(&mut self, shape: ShapeHandle)
+		-> Result<(), HandleError>
+	{
+		let (shapes, ind, x) = match shape {
 			ShapeHandle::Opaque(x) => {
-				let x = *x as usize; // for indexing
-				self.opaque_vec[x].transform = transform;
-				ffi::copy_memory(&self.vw.connection,
-					self.opaque_vec[x].instance.uniform_memory.memory(),
-					&uniform);
+				(&mut self.opaque_vec, &mut self.opaque_ind, x)
 			},
 			ShapeHandle::Alpha(x) => {
-				let x = *x as usize; // for indexing
-				self.alpha_vec[x].transform = transform;
-				ffi::copy_memory(&self.vw.connection,
-					self.alpha_vec[x].instance.uniform_memory.memory(),
-					&uniform);
+				(&mut self.alpha_vec, &mut self.alpha_ind, x)
 			},
 			ShapeHandle::Gui(x) => {
-				let x = *x as usize; // for indexing
-				self.gui_vec[x].transform = transform;
-				ffi::copy_memory(&self.vw.connection,
-					self.gui_vec[x].instance.uniform_memory.memory(),
-					&uniform);
+				(&mut self.gui_vec, &mut self.gui_ind, x)
 			},
+		};
+
+		let index = shapes.index(x)? as u32;
+		let shape = shapes.remove(x)?;
+		ind.retain(|y| *y != index);
+
+		for handle in shape.sources.handles() {
+			let users = self.users.get_mut(&handle).map(|users| {
+				*users -= 1;
+				*users
+			});
+
+			if users == Some(0) {
+				self.users.remove(&handle);
+			}
 		}
+
+		for sprite in shape.instances {
+			self.retire(Garbage::Sprite(sprite));
+		}
+		Ok(())
+	}
+
+	/// Check that `shape` refers to a shape that hasn't been dropped.
+	pub fn check_shape(&self, shape: &ShapeHandle) -> Result<(), HandleError> {
+		match *shape {
+			ShapeHandle::Opaque(x) => self.opaque_vec.index(x),
+			ShapeHandle::Alpha(x) => self.alpha_vec.index(x),
+			ShapeHandle::Gui(x) => self.gui_vec.index(x),
+		}.map(|_| ())
+	}
+
+	pub fn transform(&mut self, shape: &ShapeHandle, transform: Transform)
+		-> Result<(), Error>
+	{
+		let every = self.every_frame();
+		let shape = match *shape {
+			ShapeHandle::Opaque(x) => self.opaque_vec.get_mut(x)?,
+			ShapeHandle::Alpha(x) => self.alpha_vec.get_mut(x)?,
+			ShapeHandle::Gui(x) => self.gui_vec.get_mut(x)?,
+		};
+
+		// Written into each frame's sprite once it's not in flight.
+		shape.transform = transform;
+		shape.stale = every;
+		Ok(())
 	}
 
 	pub fn set_camera(&mut self, xyz: Vec3, rxyz: Vec3) {
@@ -1069,30 +2453,124 @@ impl Renderer {
 	}
 
 	pub fn camera(&mut self) {
-		self.camera_memory.data.mat4 = Transform::IDENTITY
+		let mat4 = Transform::IDENTITY
 			.t(vec3!()-self.xyz) // Move camera - TODO: negation operator?
 			.r(vec3!()-self.rotate_xyz) // Rotate camera - TODO: negation operator?
 			.m(self.projection.0) // Apply projection to camera
 			.into(); // convert to f32 array
 
-		self.camera_memory.update(&self.vw.connection);
+		// Written into each frame's camera once it's not in flight.
+		self.view = mat4;
+		self.stale = self.every_frame();
 	}
 
 	pub fn fog(&mut self, fog: (f32, f32)) -> () {
-		self.effect_memory.data.fogr = [fog.0, fog.1];
-		let rgb = (self.clear_color.0, self.clear_color.1,
-			self.clear_color.2);
-		self.fog_color(rgb.0, rgb.1, rgb.2);
+		self.fog = fog;
+		self.stale = self.every_frame();
 	}
 
-	fn fog_color(&mut self, r: f32, g: f32, b: f32) {
-		self.effect_memory.data.fogc = [r, g, b, 1.0];
-		self.effect_memory.update(&self.vw.connection);
+	/// Get a bit for each frame in flight.
+	fn every_frame(&self) -> u32 {
+		(1 << self.frames.len()) - 1
+	}
+
+	/// Write the camera, fog and uniforms that changed since frame `frame`
+	/// was last drawn into its own copies.  It must not be in flight.
+	fn write_frame(&mut self, frame: usize) -> Result<(), Error> {
+		let bit = 1 << frame;
+
+		if self.stale & bit != 0 {
+			let (r, g, b) = self.clear_color;
+			let (ref mut camera, ref mut effect) =
+				self.cameras[frame];
+
+			self.vw.connection.camera_write(camera,
+				TransformUniform { mat4: self.view });
+			self.vw.connection.effect_write(effect, FogUniform {
+				fogc: [r, g, b, 1.0],
+				fogr: [self.fog.0, self.fog.1],
+			});
+			self.stale &= !bit;
+		}
+
+		for shapes in [&mut self.opaque_vec, &mut self.alpha_vec,
+			&mut self.gui_vec].iter_mut()
+		{
+			for shape in shapes.iter_mut() {
+				if shape.stale & bit == 0 {
+					continue;
+				}
+
+				// Every uniform starts with the transform.
+				let sprite = &shape.instances[frame];
+				shape.uniform.write(&mut self.vw.connection,
+					sprite)?;
+				self.vw.connection.sprite_write(sprite,
+					&TransformUniform {
+						mat4: shape.transform.into(),
+					})?;
+				shape.stale &= !bit;
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Wait for the frames in flight that drew `texture` to finish,
+	/// before writing it.  Frames that didn't draw it don't read it.
+	fn finish_texture(&mut self, texture: u32) -> Result<(), Error> {
+		let mut result = Ok(());
+
+		for frame in self.frames.iter_mut() {
+			if frame.textures.contains(&texture) {
+				result = result.and(wait_frame(
+					&mut self.vw.connection, frame));
+			}
+		}
+
+		result
+	}
+
+	/// Get the device this renderer draws with.
+	pub fn device(&self) -> &D {
+		&self.vw.connection
+	}
+
+	/// Get the device this renderer draws with, mutably.
+	pub fn device_mut(&mut self) -> &mut D {
+		&mut self.vw.connection
 	}
 }
 
-impl Drop for Renderer {
+impl<D: Device> Drop for Renderer<D> {
 	fn drop(&mut self) -> () {
+		let frames = mem::replace(&mut self.frames, Vec::new());
+		drop_frames(&mut self.vw.connection, frames);
+
+		// Free the sprites of shapes that were never dropped.
+		for shapes in [&mut self.opaque_vec, &mut self.alpha_vec,
+			&mut self.gui_vec].iter_mut()
+		{
+			for shape in shapes.drain() {
+				for sprite in shape.instances {
+					self.vw.connection.sprite_drop(sprite);
+				}
+			}
+		}
+
+		for (_, sampler) in self.samplers.drain() {
+			self.vw.connection.sampler_drop(sampler);
+		}
+
 		swapchain_delete(&mut self.vw);
+
+		// Save the pipeline cache for the next run.
+		if let Some(ref dir) = self.vw.config.pipeline_cache {
+			let data = self.vw.connection.pipeline_cache_data(
+				&self.vw.pipeline_cache);
+
+			cache::save(dir, &data);
+		}
+		self.vw.connection.pipeline_cache_drop(self.vw.pipeline_cache);
 	}
 }
@@ -0,0 +1,801 @@
+// Copyright Jeron A. Lau 2018.
+// Dual-licensed under either the MIT License or the Boost Software License,
+// Version 1.0.  (See accompanying file LICENSE_1_0.txt or copy at
+// https://www.boost.org/LICENSE_1_0.txt)
+
+use std::{ mem, slice };
+use std::collections::HashMap;
+
+use adi_gpu_base::*;
+
+use asi_vulkan::types::*;
+use asi_vulkan::TransformUniform;
+use asi_vulkan::FogUniform;
+
+use PresentMode;
+use SamplerDesc;
+
+use super::cache;
+use super::Device;
+use super::Error;
+use super::MAX_IMAGES;
+
+/// The vendor ID, device ID and pipeline cache UUID of every `Recorder`.
+const PIPELINE_CACHE_ID: (u32, u32, [u8; 16]) = (0x1AD1, 0x6B0, [0xAD; 16]);
+
+/// A call made on a `Recorder`.  Every GPU object is identified by the id
+/// the `Recorder` handed out when it was created.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Call {
+	Color([f32; 3]),
+	SwapchainNew {
+		present_mode: PresentMode,
+		image_count: u32,
+		samples: VkSampleCount,
+		render_pass: u32,
+	},
+	SwapchainDrop { render_pass: u32 },
+	OffscreenNew {
+		width: u16,
+		height: u16,
+		samples: VkSampleCount,
+		render_pass: u32,
+	},
+	OffscreenDrop { render_pass: u32 },
+	Image {
+		image: u32,
+		width: u32,
+		height: u32,
+		levels: u32,
+		format: VkFormat,
+	},
+	ImageWrite { image: u32, width: usize, height: usize },
+	ImageWriteRegion {
+		image: u32,
+		x: u16,
+		y: u16,
+		width: u16,
+		height: u16,
+	},
+	ImageWriteDepth {
+		image: u32,
+		x: u16,
+		y: u16,
+		width: u16,
+		height: u16,
+	},
+	ImageCopy { src: u32, dst: u32, level: u32 },
+	ImageCopyRegion {
+		src: u32,
+		dst: u32,
+		x: u16,
+		y: u16,
+		width: u16,
+		height: u16,
+	},
+	Mipmaps { image: u32, levels: u32 },
+	ImageReadBack { src: u32, dst: u32 },
+	ImageDrop(u32),
+	Buffer { buffer: u32, data: Vec<f32> },
+	BufferDrop(u32),
+	PipelineCache { cache: u32, data: Vec<u8> },
+	PipelineCacheDrop(u32),
+	Sampler { sampler: u32, desc: SamplerDesc },
+	SamplerDrop(u32),
+	Shader { shader: u32, len: usize },
+	Style {
+		style: u32,
+		render_pass: u32,
+		cache: u32,
+		textures: u32,
+		vertex_buffers: u32,
+		alpha: bool,
+		samples: VkSampleCount,
+	},
+	Sprite {
+		sprite: u32,
+		style: u32,
+		texture: Option<u32>,
+		sampler: Option<u32>,
+		uniform: Vec<u8>,
+	},
+	SpriteWrite { sprite: u32, uniform: Vec<u8> },
+	SpriteRestyle { sprite: u32, style: u32 },
+	SpriteDrop(u32),
+	CameraWrite([f32; 16]),
+	EffectWrite { fogc: [f32; 4], fogr: [f32; 2] },
+	FenceWait(u32),
+	NextImage(u32),
+	DrawBegin { command_buffer: u32, render_pass: u32, image: u32 },
+	BindVb(Vec<u32>),
+	BindSprite(u32),
+	Draw { vertex_count: u32, first_vertex: u32 },
+	DrawEnd,
+	QueueSubmit { command_buffer: u32, fence: u32 },
+	QueuePresent(u32),
+	DeviceReset,
+	Name { object: u32, name: String },
+}
+
+/// A `Device` that doesn't touch the GPU, but records every call made on
+/// it, so the renderer can be tested on machines without one.
+pub struct Recorder {
+	calls: Vec<Call>,
+	next_id: u32,
+	next_image: u32,
+	image_count: u32,
+	sampled: bool,
+	linear_blit: bool,
+	present_modes: Vec<PresentMode>,
+	image_counts: (u32, u32),
+	sample_counts: u32,
+	max_anisotropy: u8,
+	unsupported: Vec<VkFormat>,
+	layouts: HashMap<u32, (usize, usize, usize)>, // Width, height, bpp
+	pixels: HashMap<(u32, u32), Vec<u8>>, // By image and mip level
+	caches: HashMap<u32, Vec<u8>>,
+	failure: Option<Error>,
+	suboptimal: bool,
+}
+
+impl Recorder {
+	/// Create a new `Recorder` that can sample linear tiled images.
+	pub fn new() -> Recorder {
+		Recorder {
+			calls: Vec::new(),
+			next_id: 1,
+			next_image: 0,
+			image_count: 1,
+			sampled: true,
+			linear_blit: true,
+			present_modes: vec![PresentMode::Fifo,
+				PresentMode::Mailbox, PresentMode::Immediate],
+			image_counts: (2, 0),
+			sample_counts: 0b1111,
+			max_anisotropy: 16,
+			unsupported: Vec::new(),
+			layouts: HashMap::new(),
+			pixels: HashMap::new(),
+			caches: HashMap::new(),
+			failure: None,
+			suboptimal: false,
+		}
+	}
+
+	/// Pretend linear tiled images can't be sampled, so that textures take
+	/// the staged path.
+	pub fn staged(mut self) -> Recorder {
+		self.sampled = false;
+		self
+	}
+
+	/// Pretend textures can't be blitted with linear filtering, so that mip
+	/// levels are made on the CPU.
+	pub fn no_linear_blit(mut self) -> Recorder {
+		self.linear_blit = false;
+		self
+	}
+
+	/// Pretend images of `format` can't be made at all.
+	pub fn unsupported(mut self, format: VkFormat) -> Recorder {
+		self.unsupported.push(format);
+		self
+	}
+
+	/// Pretend the surface only supports `present_modes`, and between
+	/// `min` and `max` swapchain images (0 for no limit).
+	pub fn surface(mut self, present_modes: Vec<PresentMode>, min: u32,
+		max: u32) -> Recorder
+	{
+		self.present_modes = present_modes;
+		self.image_counts = (min, max);
+		self
+	}
+
+	/// Pretend the framebuffers only support the sample counts in the
+	/// `VkSampleCountFlags` `counts`.
+	pub fn sample_counts(mut self, counts: u32) -> Recorder {
+		self.sample_counts = counts;
+		self
+	}
+
+	/// Pretend anisotropic filtering can take at most `max` samples.
+	pub fn anisotropy(mut self, max: u8) -> Recorder {
+		self.max_anisotropy = max;
+		self
+	}
+
+	/// Get the pipeline cache header this device accepts.
+	pub fn pipeline_cache_header() -> Vec<u8> {
+		cache::header(PIPELINE_CACHE_ID)
+	}
+
+	/// Make the next call that can fail return `error`.
+	pub fn fail(&mut self, error: Error) {
+		self.failure = Some(error);
+	}
+
+	/// Make the next swapchain image acquired suboptimal.
+	pub fn suboptimal(&mut self) {
+		self.suboptimal = true;
+	}
+
+	/// Get every call recorded so far.
+	pub fn calls(&self) -> &[Call] {
+		&self.calls
+	}
+
+	/// Get the pixels of an image (its first mip level), if any were
+	/// written or copied into it.
+	pub fn pixels(&self, image: u32) -> Option<&[u8]> {
+		self.level_pixels(image, 0)
+	}
+
+	/// Get the pixels of mip level `level` of an image, if any were
+	/// written or copied into it.  Rows are tightly packed.
+	pub fn level_pixels(&self, image: u32, level: u32) -> Option<&[u8]> {
+		self.pixels.get(&(image, level)).map(|pixels| &pixels[..])
+	}
+
+	/// Forget every call recorded so far.
+	pub fn clear(&mut self) {
+		self.calls.clear();
+	}
+
+	/// Fail if `fail()` was called.
+	fn result(&mut self) -> Result<(), Error> {
+		match self.failure.take() {
+			Some(error) => Err(error),
+			None => Ok(()),
+		}
+	}
+
+	/// Get the row pitch of mip level `level` of an image, and its pixels
+	/// (transparent black until they're written).
+	fn level(&mut self, image: u32, level: u32) -> (usize, &mut Vec<u8>) {
+		let (width, height, bpp) = self.layouts[&image];
+		let width = (width >> level).max(1);
+		let height = (height >> level).max(1);
+
+		(width * bpp, self.pixels.entry((image, level))
+			.or_insert_with(|| vec![0; width * height * bpp]))
+	}
+
+	fn id(&mut self) -> u32 {
+		let id = self.next_id;
+		self.next_id += 1;
+		id
+	}
+
+	/// Make a multisampling buffer, unless there's one sample per pixel.
+	fn ms_image(&mut self, samples: VkSampleCount) -> Option<u32> {
+		if samples == VkSampleCount::Sc1 {
+			None
+		} else {
+			Some(self.id())
+		}
+	}
+}
+
+impl Default for Recorder {
+	fn default() -> Recorder {
+		Recorder::new()
+	}
+}
+
+fn bytes<T>(data: &T) -> Vec<u8> {
+	unsafe {
+		slice::from_raw_parts(data as *const T as *const u8,
+			mem::size_of::<T>())
+	}.to_vec()
+}
+
+/// Get the size of a pixel of `format` in bytes.
+fn pixel_size(format: VkFormat) -> usize {
+	match format {
+		VkFormat::R8Unorm => 1,
+		VkFormat::R8g8Unorm => 2,
+		VkFormat::R16g16b16a16Sfloat => 8,
+		VkFormat::R32g32b32a32Sfloat => 16,
+		_ => 4,
+	}
+}
+
+/// Copy `height` rows of `len` bytes at `src_at` in `src` to `dst_at` in
+/// `dst`.  Positions are a byte offset into a row and a row, and rows are
+/// `src_pitch` and `dst_pitch` bytes long.
+fn copy_rect(src: &[u8], src_pitch: usize, src_at: (usize, usize),
+	dst: &mut [u8], dst_pitch: usize, dst_at: (usize, usize),
+	(len, height): (usize, usize))
+{
+	for row in 0..height {
+		let from = (src_at.1 + row) * src_pitch + src_at.0;
+		let to = (dst_at.1 + row) * dst_pitch + dst_at.0;
+
+		dst[to..to + len].copy_from_slice(&src[from..from + len]);
+	}
+}
+
+impl Device for Recorder {
+	type Image = u32;
+	type Buffer = u32;
+	type VertexBuffer = u32;
+	type Shader = u32;
+	type Style = u32;
+	type Sprite = u32;
+	type CameraMemory = ();
+	type EffectMemory = ();
+	type RenderPass = u32;
+	type Framebuffer = u32;
+	type PresentImage = u32;
+	type ImageView = u32;
+	type Fence = u32;
+	type Semaphore = u32;
+	type CommandBuffer = u32;
+	type PipelineCache = u32;
+	type Sampler = u32;
+
+	fn sampled(&self) -> bool {
+		self.sampled
+	}
+
+	fn color(&mut self, rgb: Vec3) {
+		self.calls.push(Call::Color([rgb.x, rgb.y, rgb.z]));
+	}
+
+	fn present_modes(&mut self) -> Vec<PresentMode> {
+		self.present_modes.clone()
+	}
+
+	fn image_counts(&mut self) -> (u32, u32) {
+		self.image_counts
+	}
+
+	fn present_format(&self) -> VkFormat {
+		VkFormat::R8g8b8a8Unorm
+	}
+
+	fn sample_counts(&self) -> u32 {
+		self.sample_counts
+	}
+
+	fn swapchain_new(&mut self, present_mode: PresentMode,
+		image_count: &mut u32, samples: VkSampleCount,
+		present_images: &mut [u32; MAX_IMAGES],
+		present_image_views: &mut [u32; MAX_IMAGES],
+		frame_buffers: &mut [u32; MAX_IMAGES])
+		-> (u32, Option<u32>, u32)
+	{
+		for i in 0..*image_count as usize {
+			present_images[i] = self.id();
+			present_image_views[i] = self.id();
+			frame_buffers[i] = self.id();
+		}
+
+		let depth_image = self.id();
+		let ms_image = self.ms_image(samples);
+		let render_pass = self.id();
+
+		self.image_count = *image_count;
+		self.next_image = 0;
+		self.calls.push(Call::SwapchainNew {
+			present_mode, image_count: *image_count, samples,
+			render_pass
+		});
+
+		(depth_image, ms_image, render_pass)
+	}
+
+	fn swapchain_drop(&mut self, _frame_buffers: &[u32; MAX_IMAGES],
+		_present_image_views: &[u32; MAX_IMAGES], render_pass: u32,
+		_image_count: u32)
+	{
+		self.calls.push(Call::SwapchainDrop { render_pass });
+	}
+
+	fn offscreen_new(&mut self, width: u16, height: u16,
+		samples: VkSampleCount,
+		present_images: &mut [u32; MAX_IMAGES],
+		present_image_views: &mut [u32; MAX_IMAGES],
+		frame_buffers: &mut [u32; MAX_IMAGES])
+		-> (u32, u32, Option<u32>, u32)
+	{
+		let color_image = self.id();
+		present_images[0] = color_image;
+		present_image_views[0] = self.id();
+		frame_buffers[0] = self.id();
+
+		let depth_image = self.id();
+		let ms_image = self.ms_image(samples);
+		let render_pass = self.id();
+
+		self.calls.push(Call::OffscreenNew {
+			width, height, samples, render_pass
+		});
+
+		(color_image, depth_image, ms_image, render_pass)
+	}
+
+	fn offscreen_drop(&mut self, _frame_buffers: &[u32; MAX_IMAGES],
+		_present_image_views: &[u32; MAX_IMAGES], render_pass: u32)
+	{
+		self.calls.push(Call::OffscreenDrop { render_pass });
+	}
+
+	fn image(&mut self, width: u32, height: u32, levels: u32,
+		format: VkFormat, _tiling: VkImageTiling, _usage: VkImageUsage,
+		_layout: VkImageLayout, _properties: u32,
+		_samples: VkSampleCount) -> Result<u32, Error>
+	{
+		self.result()?;
+
+		let image = self.id();
+		self.layouts.insert(image, (width as usize, height as usize,
+			pixel_size(format)));
+		self.calls.push(Call::Image {
+			image, width, height, levels, format
+		});
+		Ok(image)
+	}
+
+	fn image_pitch(&mut self, image: &u32) -> u32 {
+		// Rows are tightly packed.
+		let (width, _, bpp) = self.layouts[image];
+
+		(width * bpp) as u32
+	}
+
+	fn image_write(&mut self, image: &u32, pixels: &[u8], width: usize,
+		height: usize, _pitch: usize, bpp: usize) -> Result<(), Error>
+	{
+		self.result()?;
+
+		let (pitch, to) = self.level(*image, 0);
+		copy_rect(pixels, width * bpp, (0, 0), to, pitch, (0, 0),
+			(width * bpp, height));
+		self.calls.push(Call::ImageWrite {
+			image: *image, width, height
+		});
+		Ok(())
+	}
+
+	fn image_write_region(&mut self, image: &u32, pixels: &[u8],
+		(x, y): (u16, u16), (width, height): (u16, u16), pitch: usize,
+		bpp: usize) -> Result<(), Error>
+	{
+		self.result()?;
+
+		let len = width as usize * bpp;
+		let (_, to) = self.level(*image, 0);
+		copy_rect(pixels, len, (0, 0), to, pitch,
+			(x as usize * bpp, y as usize), (len, height as usize));
+		self.calls.push(Call::ImageWriteRegion {
+			image: *image, x, y, width, height
+		});
+		Ok(())
+	}
+
+	fn image_write_depth(&mut self, image: &u32, pixels: &[u8],
+		(x, y): (u16, u16), (width, height): (u16, u16))
+		-> Result<(), Error>
+	{
+		self.result()?;
+
+		let len = width as usize * 4;
+		let (pitch, to) = self.level(*image, 0);
+		copy_rect(pixels, len, (0, 0), to, pitch,
+			(x as usize * 4, y as usize), (len, height as usize));
+		self.calls.push(Call::ImageWriteDepth {
+			image: *image, x, y, width, height
+		});
+		Ok(())
+	}
+
+	fn image_copy_region(&mut self, src: &u32, dst: &u32, x: u16, y: u16,
+		width: u16, height: u16)
+	{
+		let (pitch, from) = self.level(*src, 0);
+		let from = from.clone();
+		let bpp = self.layouts[src].2;
+		let at = (x as usize * bpp, y as usize);
+		let (to_pitch, to) = self.level(*dst, 0);
+		copy_rect(&from, pitch, at, to, to_pitch, at,
+			(width as usize * bpp, height as usize));
+		self.calls.push(Call::ImageCopyRegion {
+			src: *src, dst: *dst, x, y, width, height
+		});
+	}
+
+	fn image_copy(&mut self, src: &u32, dst: &u32, width: u16,
+		height: u16, level: u32)
+	{
+		let (pitch, from) = self.level(*src, 0);
+		let from = from.clone();
+		let len = width as usize * self.layouts[src].2;
+		let (to_pitch, to) = self.level(*dst, level);
+		copy_rect(&from, pitch, (0, 0), to, to_pitch, (0, 0),
+			(len, height as usize));
+		self.calls.push(Call::ImageCopy {
+			src: *src, dst: *dst, level
+		});
+	}
+
+	fn linear_blit(&self, _format: VkFormat) -> bool {
+		self.linear_blit
+	}
+
+	fn format_supported(&self, format: VkFormat, tiling: VkImageTiling,
+		_usage: VkImageUsage) -> bool
+	{
+		match (format, tiling) {
+			// Like real GPUs, depth can't be linear tiled.
+			(VkFormat::D32Sfloat, VkImageTiling::Linear) => false,
+			_ => !self.unsupported.contains(&format),
+		}
+	}
+
+	fn image_mipmaps(&mut self, image: &u32, _width: u16, _height: u16,
+		levels: u32)
+	{
+		self.calls.push(Call::Mipmaps { image: *image, levels });
+	}
+
+	fn image_read_back(&mut self, src: u32, dst: &u32, _width: u16,
+		_height: u16)
+	{
+		self.calls.push(Call::ImageReadBack { src, dst: *dst });
+	}
+
+	fn image_read(&mut self, image: &u32, rgba: &mut [u8], _width: usize,
+		_height: usize, _pitch: usize) -> Result<(), Error>
+	{
+		self.result()?;
+
+		// Images that were never written read back as transparent black.
+		if let Some(pixels) = self.pixels.get(&(*image, 0)) {
+			if pixels.len() == rgba.len() {
+				rgba.copy_from_slice(pixels);
+			}
+		}
+		Ok(())
+	}
+
+	fn image_drop(&mut self, image: u32) {
+		self.layouts.remove(&image);
+		self.pixels.retain(|&(i, _), _| i != image);
+		self.calls.push(Call::ImageDrop(image));
+	}
+
+	fn buffer(&mut self, data: &[f32]) -> Result<u32, Error> {
+		self.result()?;
+
+		let buffer = self.id();
+		self.calls.push(Call::Buffer { buffer, data: data.to_vec() });
+		Ok(buffer)
+	}
+
+	fn buffer_drop(&mut self, buffer: u32) {
+		self.calls.push(Call::BufferDrop(buffer));
+	}
+
+	fn vertex_buffer(&self, buffer: &u32) -> u32 {
+		*buffer
+	}
+
+	fn pipeline_cache_id(&self) -> (u32, u32, [u8; 16]) {
+		PIPELINE_CACHE_ID
+	}
+
+	fn pipeline_cache(&mut self, data: &[u8]) -> u32 {
+		let cache = self.id();
+		self.calls.push(Call::PipelineCache {
+			cache, data: data.to_vec()
+		});
+		// Like Vulkan, an empty cache still has a header.
+		self.caches.insert(cache, if data.is_empty() {
+			cache::header(PIPELINE_CACHE_ID)
+		} else {
+			data.to_vec()
+		});
+		cache
+	}
+
+	fn pipeline_cache_data(&mut self, cache: &u32) -> Vec<u8> {
+		self.caches[cache].clone()
+	}
+
+	fn pipeline_cache_drop(&mut self, cache: u32) {
+		self.caches.remove(&cache);
+		self.calls.push(Call::PipelineCacheDrop(cache));
+	}
+
+	fn max_anisotropy(&self) -> u8 {
+		self.max_anisotropy
+	}
+
+	fn sampler(&mut self, desc: &SamplerDesc) -> u32 {
+		let sampler = self.id();
+		self.calls.push(Call::Sampler { sampler, desc: *desc });
+		sampler
+	}
+
+	fn sampler_drop(&mut self, sampler: u32) {
+		self.calls.push(Call::SamplerDrop(sampler));
+	}
+
+	fn shader(&mut self, spirv: &[u8]) -> u32 {
+		let shader = self.id();
+		self.calls.push(Call::Shader { shader, len: spirv.len() });
+		shader
+	}
+
+	fn style(&mut self, render_pass: u32, cache: &u32, _vert: &u32,
+		_frag: &u32, textures: u32, vertex_buffers: u32, alpha: bool,
+		samples: VkSampleCount) -> u32
+	{
+		let style = self.id();
+		self.calls.push(Call::Style {
+			style, render_pass, cache: *cache, textures,
+			vertex_buffers, alpha, samples
+		});
+		style
+	}
+
+	fn sprite<T: Clone>(&mut self, style: &u32, uniform: T, _camera: &(),
+		_effect: Option<&()>, texture: Option<(u32, u32)>,
+		_has_texture: bool) -> u32
+	{
+		let sprite = self.id();
+		self.calls.push(Call::Sprite {
+			sprite, style: *style,
+			texture: texture.map(|(image, _)| image),
+			sampler: texture.map(|(_, sampler)| sampler),
+			uniform: bytes(&uniform),
+		});
+		sprite
+	}
+
+	fn sprite_write<T: Clone>(&mut self, sprite: &u32, uniform: &T)
+		-> Result<(), Error>
+	{
+		self.result()?;
+
+		self.calls.push(Call::SpriteWrite {
+			sprite: *sprite, uniform: bytes(uniform)
+		});
+		Ok(())
+	}
+
+	fn sprite_restyle(&mut self, sprite: &mut u32, style: &u32) {
+		self.calls.push(Call::SpriteRestyle {
+			sprite: *sprite, style: *style
+		});
+	}
+
+	fn sprite_drop(&mut self, sprite: u32) {
+		self.calls.push(Call::SpriteDrop(sprite));
+	}
+
+	fn camera(&mut self, _rgba: (f32, f32, f32, f32), _fog: (f32, f32))
+		-> ((), ())
+	{
+		((), ())
+	}
+
+	fn camera_write(&mut self, _memory: &mut (), camera: TransformUniform) {
+		self.calls.push(Call::CameraWrite(camera.mat4));
+	}
+
+	fn effect_write(&mut self, _memory: &mut (), effect: FogUniform) {
+		self.calls.push(Call::EffectWrite {
+			fogc: effect.fogc, fogr: effect.fogr
+		});
+	}
+
+	fn semaphore(&mut self) -> u32 {
+		self.id()
+	}
+
+	fn semaphore_drop(&mut self, _semaphore: u32) { }
+
+	fn fence(&mut self) -> u32 {
+		self.id()
+	}
+
+	fn fence_wait(&mut self, fence: &u32) -> Result<(), Error> {
+		self.result()?;
+
+		self.calls.push(Call::FenceWait(*fence));
+		Ok(())
+	}
+
+	fn fence_reset(&mut self, _fence: &u32) { }
+
+	fn fence_drop(&mut self, _fence: u32) { }
+
+	fn command_buffer(&mut self) -> u32 {
+		self.id()
+	}
+
+	fn command_buffer_drop(&mut self, _command_buffer: u32) { }
+
+	fn next_image(&mut self, _acquired: &u32)
+		-> Result<(u32, bool), Error>
+	{
+		self.result()?;
+
+		let image = self.next_image;
+		self.next_image = (self.next_image + 1) % self.image_count;
+		self.calls.push(Call::NextImage(image));
+		Ok((image, mem::replace(&mut self.suboptimal, false)))
+	}
+
+	fn draw_begin(&mut self, command_buffer: &u32, render_pass: u32,
+		image: u32, _frame_buffer: u32)
+	{
+		self.calls.push(Call::DrawBegin {
+			command_buffer: *command_buffer, render_pass, image
+		});
+	}
+
+	fn bind_vb(&mut self, buffers: &[u32]) {
+		self.calls.push(Call::BindVb(buffers.to_vec()));
+	}
+
+	fn bind_sprite(&mut self, sprite: &u32) {
+		self.calls.push(Call::BindSprite(*sprite));
+	}
+
+	fn draw(&mut self, vertex_count: u32, first_vertex: u32) {
+		self.calls.push(Call::Draw { vertex_count, first_vertex });
+	}
+
+	fn draw_end(&mut self, _image: Option<u32>) {
+		self.calls.push(Call::DrawEnd);
+	}
+
+	fn queue_submit(&mut self, command_buffer: &u32, _wait: Option<&u32>,
+		_signal: Option<&u32>, fence: &u32) -> Result<(), Error>
+	{
+		self.result()?;
+
+		self.calls.push(Call::QueueSubmit {
+			command_buffer: *command_buffer, fence: *fence
+		});
+		Ok(())
+	}
+
+	fn queue_present(&mut self, image_index: u32, _wait: &u32)
+		-> Result<(), Error>
+	{
+		self.result()?;
+
+		self.calls.push(Call::QueuePresent(image_index));
+		Ok(())
+	}
+
+	fn device_reset(&mut self) -> Result<(), Error> {
+		self.result()?;
+
+		self.calls.push(Call::DeviceReset);
+		Ok(())
+	}
+
+	fn name_buffer(&mut self, buffer: &u32, name: &str) {
+		self.calls.push(Call::Name {
+			object: *buffer, name: name.to_string()
+		});
+	}
+
+	fn name_image(&mut self, image: &u32, name: &str) {
+		self.calls.push(Call::Name {
+			object: *image, name: name.to_string()
+		});
+	}
+
+	fn name_style(&mut self, style: &u32, name: &str) {
+		self.calls.push(Call::Name {
+			object: *style, name: name.to_string()
+		});
+	}
+}
@@ -0,0 +1,213 @@
+// Copyright Jeron A. Lau 2018.
+// Dual-licensed under either the MIT License or the Boost Software License,
+// Version 1.0.  (See accompanying file LICENSE_1_0.txt or copy at
+// https://www.boost.org/LICENSE_1_0.txt)
+
+use std::{ error, fmt };
+use std::collections::VecDeque;
+
+use adi_gpu_base::*;
+
+// A handle is packed into 32 bits: 4 bits of kind, 8 bits of generation and
+// 20 bits of index.  Generation 0 is never used, so 0 is never a valid handle.
+// A slot is retired instead of wrapping around to generation 1, until every
+// index has been used.  Then the retired slots start a new epoch, back at
+// generation 1.  So an old handle can only refer to a new item once all 255
+// generations of its slot, and all 2^20 indices, have been used.  Handles
+// only run out with 2^20 items of one kind at once.
+const INDEX_BITS: u32 = 20;
+const GENERATION_BITS: u32 = 8;
+const INDEX_MASK: u32 = (1 << INDEX_BITS) - 1;
+const GENERATION_MASK: u32 = (1 << GENERATION_BITS) - 1;
+
+/// What a handle refers to, so a handle for one kind of object can't be used
+/// as another.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Kind {
+	Model = 1,
+	Texture,
+	TexCoords,
+	Gradient,
+	Opaque,
+	Alpha,
+	Gui,
+	Style,
+}
+
+/// Why a handle couldn't be used.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum HandleError {
+	/// The handle is for a different kind of object, or wasn't made by
+	/// this `Display`.
+	Foreign,
+	/// The object the handle referred to was dropped.
+	Stale,
+	/// The object can't be dropped, because shapes made from it haven't
+	/// been dropped yet.
+	InUse,
+}
+
+impl fmt::Display for HandleError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match *self {
+			HandleError::Foreign => "handle is for another object",
+			HandleError::Stale => "handle is for a dropped object",
+			HandleError::InUse => "object is used by shapes",
+		})
+	}
+}
+
+impl error::Error for HandleError { }
+
+/// One slot in `Slots`.
+pub struct Slot<T> {
+	generation: u32,
+	item: Option<T>,
+}
+
+impl<T: Point> Point for Slot<T> {
+	fn point(&self) -> Vec3 {
+		// Empty slots are never sorted.
+		self.item.as_ref().map(|item| item.point())
+			.unwrap_or(vec3!(0.0, 0.0, 0.0))
+	}
+}
+
+/// Pack a handle.
+fn handle(kind: Kind, generation: u32, index: usize) -> u32 {
+	((kind as u32) << (INDEX_BITS + GENERATION_BITS))
+		| (generation << INDEX_BITS) | index as u32
+}
+
+/// A list of items that hands out generation-checked handles, and reuses the
+/// indices of removed items.
+pub struct Slots<T> {
+	kind: Kind,
+	slots: Vec<Slot<T>>,
+	free: VecDeque<usize>, // Oldest first, so generations are used evenly
+	retired: Vec<usize>, // Every generation used, until the next epoch
+}
+
+impl<T> Slots<T> {
+	pub fn new(kind: Kind) -> Slots<T> {
+		Slots {
+			kind, slots: Vec::new(), free: VecDeque::new(),
+			retired: Vec::new(),
+		}
+	}
+
+	/// Add an item, returning its handle.  Gives the item back if every
+	/// index is in use.
+	pub fn insert(&mut self, item: T) -> Result<u32, T> {
+		// Every index has been used, so start a new epoch.
+		let full = self.slots.len() > INDEX_MASK as usize;
+		if full && self.free.is_empty() {
+			for index in self.retired.drain(..) {
+				self.slots[index].generation = 1;
+				self.free.push_back(index);
+			}
+		}
+
+		let index = if let Some(index) = self.free.pop_front() {
+			self.slots[index].item = Some(item);
+			index
+		} else if self.slots.len() <= INDEX_MASK as usize {
+			self.slots.push(Slot {
+				generation: 1, item: Some(item)
+			});
+			self.slots.len() - 1
+		} else {
+			return Err(item);
+		};
+
+		Ok(handle(self.kind, self.slots[index].generation, index))
+	}
+
+	/// Get the index of the slot `handle` refers to, if it's still there.
+	pub fn index(&self, handle: u32) -> Result<usize, HandleError> {
+		let kind = handle >> (INDEX_BITS + GENERATION_BITS);
+		let generation = (handle >> INDEX_BITS) & GENERATION_MASK;
+		let index = (handle & INDEX_MASK) as usize;
+
+		if kind != self.kind as u32 || generation == 0 {
+			return Err(HandleError::Foreign);
+		}
+
+		match self.slots.get(index) {
+			Some(slot) if slot.generation == generation
+				&& slot.item.is_some() => Ok(index),
+			Some(_) => Err(HandleError::Stale),
+			None => Err(HandleError::Foreign),
+		}
+	}
+
+	/// Remove an item, freeing its index for reuse unless every
+	/// generation of it has been used, then retiring it until the next
+	/// epoch.
+	pub fn remove(&mut self, handle: u32) -> Result<T, HandleError> {
+		let index = self.index(handle)?;
+		let slot = &mut self.slots[index];
+
+		// A retired slot stays empty, at its last generation, so old
+		// handles for it are still found to be stale.
+		if slot.generation < GENERATION_MASK {
+			slot.generation += 1;
+			self.free.push_back(index);
+		} else {
+			self.retired.push(index);
+		}
+
+		Ok(slot.item.take().unwrap())
+	}
+
+	pub fn get(&self, handle: u32) -> Result<&T, HandleError> {
+		let index = self.index(handle)?;
+
+		Ok(self.slots[index].item.as_ref().unwrap())
+	}
+
+	pub fn get_mut(&mut self, handle: u32) -> Result<&mut T, HandleError> {
+		let index = self.index(handle)?;
+
+		Ok(self.slots[index].item.as_mut().unwrap())
+	}
+
+	/// Get the item at an index returned by `index()`.
+	pub fn at(&self, index: usize) -> &T {
+		self.slots[index].item.as_ref().unwrap()
+	}
+
+	pub fn get_mut_at(&mut self, index: usize) -> &mut T {
+		self.slots[index].item.as_mut().unwrap()
+	}
+
+	/// Get every item.
+	pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+		self.slots.iter_mut().filter_map(|slot| slot.item.as_mut())
+	}
+
+	/// Get every item with its handle.
+	pub fn iter_mut_handles(&mut self)
+		-> impl Iterator<Item = (u32, &mut T)>
+	{
+		let kind = self.kind;
+
+		self.slots.iter_mut().enumerate().filter_map(move |(i, slot)| {
+			let handle = handle(kind, slot.generation, i);
+
+			slot.item.as_mut().map(|item| (handle, item))
+		})
+	}
+
+	/// Remove every item.
+	pub fn drain(&mut self) -> Vec<T> {
+		self.free.clear();
+		self.retired.clear();
+		self.slots.drain(..).filter_map(|slot| slot.item).collect()
+	}
+
+	/// Get every slot, for `zsort()`.
+	pub fn slots(&self) -> &Vec<Slot<T>> {
+		&self.slots
+	}
+}
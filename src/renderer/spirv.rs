@@ -0,0 +1,286 @@
+// Copyright Jeron A. Lau 2018.
+// Dual-licensed under either the MIT License or the Boost Software License,
+// Version 1.0.  (See accompanying file LICENSE_1_0.txt or copy at
+// https://www.boost.org/LICENSE_1_0.txt)
+
+//! Just enough SPIR-V reflection to check that a shader agrees with the
+//! uniform struct and vertex buffers the `Renderer` gives it.
+
+use std::collections::HashMap;
+
+const MAGIC: u32 = 0x07230203;
+
+// Opcodes
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+
+// Decorations
+const ARRAY_STRIDE: u32 = 6;
+const MATRIX_STRIDE: u32 = 7;
+const BUILT_IN: u32 = 11;
+const LOCATION: u32 = 30;
+const BINDING: u32 = 33;
+const OFFSET: u32 = 35;
+
+// Storage classes
+const UNIFORM_CONSTANT: u32 = 0;
+const INPUT: u32 = 1;
+const UNIFORM: u32 = 2;
+
+/// What a shader expects to be given.
+#[derive(Debug, PartialEq)]
+pub struct Layout {
+	/// Size in bytes of the uniform block at binding 0, if there is one.
+	pub uniform_size: Option<u32>,
+	/// Number of vertex attributes (one per vertex buffer).
+	pub inputs: u32,
+	/// Number of textures.
+	pub textures: u32,
+}
+
+enum Type {
+	Scalar(u32), // Size in bytes
+	Vector(u32, u32), // Component type, count
+	Matrix(u32, u32), // Column type, count
+	SampledImage,
+	Array(u32, u32), // Element type, length constant
+	Struct(Vec<u32>), // Member types
+	Pointer(u32), // Pointee type
+}
+
+/// Read the layout of a SPIR-V module.
+pub fn layout(spirv: &[u8]) -> Result<Layout, String> {
+	if spirv.len() % 4 != 0 || spirv.len() < 20 {
+		return Err("Not SPIR-V: bad length".to_string());
+	}
+
+	let words: Vec<u32> = spirv.chunks(4).map(|b| {
+		b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16
+			| (b[3] as u32) << 24
+	}).collect();
+
+	if words[0] != MAGIC {
+		return Err("Not SPIR-V: bad magic number".to_string());
+	}
+
+	let mut types = HashMap::new();
+	let mut constants = HashMap::new(); // id -> first word of value
+	let mut variables = Vec::new(); // (id, pointer type, storage class)
+	let mut decorations = HashMap::new(); // (id, decoration) -> value
+	let mut member_decorations = HashMap::new(); // (id, member, dec.)
+
+	let mut i = 5;
+	while i < words.len() {
+		let opcode = words[i] & 0xFFFF;
+		let count = (words[i] >> 16) as usize;
+
+		if count == 0 || i + count > words.len() {
+			return Err("Not SPIR-V: bad instruction".to_string());
+		}
+
+		let args = &words[i + 1..i + count];
+		let arg = |n: usize| args.get(n).cloned().unwrap_or(0);
+
+		match opcode {
+			OP_TYPE_INT | OP_TYPE_FLOAT => {
+				types.insert(arg(0), Type::Scalar(arg(1) / 8));
+			}
+			OP_TYPE_VECTOR => {
+				types.insert(arg(0), Type::Vector(arg(1), arg(2)));
+			}
+			OP_TYPE_MATRIX => {
+				types.insert(arg(0), Type::Matrix(arg(1), arg(2)));
+			}
+			OP_TYPE_SAMPLED_IMAGE => {
+				types.insert(arg(0), Type::SampledImage);
+			}
+			OP_TYPE_ARRAY => {
+				types.insert(arg(0),
+					Type::Array(arg(1), arg(2)));
+			}
+			OP_TYPE_STRUCT => {
+				types.insert(arg(0), Type::Struct(args[1..].to_vec()));
+			}
+			OP_TYPE_POINTER => {
+				types.insert(arg(0), Type::Pointer(arg(2)));
+			}
+			OP_CONSTANT => {
+				constants.insert(arg(1), arg(2));
+			}
+			OP_VARIABLE => variables.push((arg(1), arg(0), arg(2))),
+			OP_DECORATE => {
+				decorations.insert((arg(0), arg(1)), arg(2));
+			}
+			OP_MEMBER_DECORATE => {
+				member_decorations.insert((arg(0), arg(1), arg(2)),
+					arg(3));
+			}
+			_ => {}
+		}
+
+		i += count;
+	}
+
+	let pointee = |pointer: u32| match types.get(&pointer) {
+		Some(&Type::Pointer(pointee)) => pointee,
+		_ => 0,
+	};
+
+	let mut layout = Layout { uniform_size: None, inputs: 0, textures: 0 };
+
+	for &(id, pointer, storage) in variables.iter() {
+		match storage {
+			INPUT => {
+				if decorations.contains_key(&(id, LOCATION))
+					&& !decorations.contains_key(&(id, BUILT_IN))
+				{
+					layout.inputs += 1;
+				}
+			}
+			UNIFORM_CONSTANT => {
+				if let Some(&Type::SampledImage)
+					= types.get(&pointee(pointer))
+				{
+					layout.textures += 1;
+				}
+			}
+			UNIFORM => {
+				if decorations.get(&(id, BINDING)) == Some(&0) {
+					let module = Module {
+						types: &types,
+						constants: &constants,
+						decorations: &decorations,
+						member_decorations:
+							&member_decorations,
+					};
+
+					layout.uniform_size = Some(module
+						.block_size(pointee(pointer))?);
+				}
+			}
+			_ => {}
+		}
+	}
+
+	Ok(layout)
+}
+
+/// The parts of a module needed to size a uniform block.
+struct Module<'a> {
+	types: &'a HashMap<u32, Type>,
+	constants: &'a HashMap<u32, u32>,
+	decorations: &'a HashMap<(u32, u32), u32>,
+	member_decorations: &'a HashMap<(u32, u32, u32), u32>,
+}
+
+impl<'a> Module<'a> {
+	/// Get the size of a uniform block (or a struct in one), up to the
+	/// end of its last member.
+	fn block_size(&self, block: u32) -> Result<u32, String> {
+		let members = match self.types.get(&block) {
+			Some(&Type::Struct(ref members)) => members,
+			_ => return Err("Uniform block isn't a struct"
+				.to_string()),
+		};
+
+		let mut size = 0;
+
+		for (i, member) in members.iter().enumerate() {
+			let i = i as u32;
+			let offset = *self.member_decorations
+				.get(&(block, i, OFFSET))
+				.ok_or("Uniform block member has no offset")?;
+			let stride = self.member_decorations
+				.get(&(block, i, MATRIX_STRIDE)).cloned();
+
+			size = size.max(offset
+				+ self.type_size(*member, stride)?);
+		}
+
+		Ok(size)
+	}
+
+	fn type_size(&self, id: u32, matrix_stride: Option<u32>)
+		-> Result<u32, String>
+	{
+		Ok(match self.types.get(&id) {
+			Some(&Type::Scalar(size)) => size,
+			Some(&Type::Vector(component, count)) => {
+				self.type_size(component, None)? * count
+			}
+			Some(&Type::Matrix(column, count)) => {
+				match matrix_stride {
+					Some(stride) => stride * count,
+					None => self.type_size(column, None)?
+						* count,
+				}
+			}
+			Some(&Type::Array(element, length)) => {
+				let length = *self.constants.get(&length)
+					.ok_or("Uniform array has no length")?;
+
+				// Every element but the last takes up a whole
+				// stride.
+				match length {
+					0 => 0,
+					_ => self.array_stride(id, element,
+						matrix_stride)? * (length - 1)
+						+ self.type_size(element,
+						matrix_stride)?,
+				}
+			}
+			Some(&Type::Struct(_)) => self.block_size(id)?,
+			_ => return Err(format!(
+				"Unsupported uniform member type {}", id)),
+		})
+	}
+
+	fn array_stride(&self, array: u32, element: u32,
+		matrix_stride: Option<u32>) -> Result<u32, String>
+	{
+		match self.decorations.get(&(array, ARRAY_STRIDE)) {
+			Some(&stride) => Ok(stride),
+			None => self.type_size(element, matrix_stride),
+		}
+	}
+}
+
+/// Check that a style's shaders match the uniform (of `uniform` bytes),
+/// textures and vertex buffers the renderer gives them.
+pub fn check(name: &str, vert: &[u8], frag: &[u8], uniform: usize,
+	textures: u32, vertex_buffers: u32) -> Result<(), String>
+{
+	let vert = layout(vert).map_err(|e| {
+		format!("{} vertex shader: {}", name, e)
+	})?;
+	let frag = layout(frag).map_err(|e| {
+		format!("{} fragment shader: {}", name, e)
+	})?;
+
+	for size in vert.uniform_size.iter().chain(frag.uniform_size.iter()) {
+		if *size as usize != uniform {
+			return Err(format!("{} style: shader uniform is {} \
+				bytes, but the renderer's is {}", name, size,
+				uniform));
+		}
+	}
+	if vert.textures + frag.textures != textures {
+		return Err(format!("{} style: shaders have {} textures, not {}",
+			name, vert.textures + frag.textures, textures));
+	}
+	if vert.inputs != vertex_buffers {
+		return Err(format!("{} style: vertex shader has {} inputs, \
+			not {}", name, vert.inputs, vertex_buffers));
+	}
+	Ok(())
+}
@@ -0,0 +1,24 @@
+// Copyright Jeron A. Lau 2018.
+// Dual-licensed under either the MIT License or the Boost Software License,
+// Version 1.0.  (See accompanying file LICENSE_1_0.txt or copy at
+// https://www.boost.org/LICENSE_1_0.txt)
+
+//! The uniforms of the built-in styles.  `build.rs` checks the shaders
+//! against them too.
+
+#[derive(Clone)] #[repr(C)] pub struct TransformFullUniform {
+	pub mat4: [f32; 16],
+	pub hcam: u32,
+}
+
+#[derive(Clone)] #[repr(C)] pub struct TransformAndFadeUniform {
+	pub mat4: [f32; 16],
+	pub fade: f32,
+	pub hcam: u32,
+}
+
+#[derive(Clone)] #[repr(C)] pub struct TransformAndColorUniform {
+	pub mat4: [f32; 16],
+	pub vec4: [f32; 4],
+	pub hcam: u32,
+}
@@ -0,0 +1,72 @@
+// Copyright Jeron A. Lau 2018.
+// Dual-licensed under either the MIT License or the Boost Software License,
+// Version 1.0.  (See accompanying file LICENSE_1_0.txt or copy at
+// https://www.boost.org/LICENSE_1_0.txt)
+
+//! How textures are sampled.
+
+/// How texels are blended together.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Filter {
+	/// Use the nearest texel, for sharp pixel art.
+	Nearest,
+	/// Blend the nearest texels.
+	Linear,
+}
+
+/// What's sampled outside of the texture (texture coordinates below 0 or
+/// above 1).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AddressMode {
+	/// Tile the texture.
+	Repeat,
+	/// Tile the texture, flipping every other tile.
+	MirroredRepeat,
+	/// Stretch the texels at the edge.
+	ClampToEdge,
+	/// Use the `SamplerDesc`'s border color.
+	ClampToBorder,
+}
+
+/// The color `AddressMode::ClampToBorder` uses.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BorderColor {
+	TransparentBlack,
+	OpaqueBlack,
+	OpaqueWhite,
+}
+
+/// How a texture is sampled.  Textures with the same `SamplerDesc` share a
+/// sampler.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SamplerDesc {
+	/// The filter when the texture is drawn smaller than it is.
+	pub min_filter: Filter,
+	/// The filter when the texture is drawn bigger than it is.
+	pub mag_filter: Filter,
+	/// The filter between mip levels.
+	pub mip_filter: Filter,
+	/// What's sampled outside of the texture horizontally.
+	pub address_u: AddressMode,
+	/// What's sampled outside of the texture vertically.
+	pub address_v: AddressMode,
+	/// How many samples anisotropic filtering may take, from 1 (off) to
+	/// 16.  Lowered to what the GPU supports.
+	pub anisotropy: u8,
+	pub border_color: BorderColor,
+}
+
+impl Default for SamplerDesc {
+	/// Linear filtering, repeating, without anisotropic filtering.
+	fn default() -> SamplerDesc {
+		SamplerDesc {
+			min_filter: Filter::Linear,
+			mag_filter: Filter::Linear,
+			mip_filter: Filter::Linear,
+			address_u: AddressMode::Repeat,
+			address_v: AddressMode::Repeat,
+			anisotropy: 1,
+			border_color: BorderColor::TransparentBlack,
+		}
+	}
+}
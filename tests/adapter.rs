@@ -0,0 +1,55 @@
+// Copyright Jeron A. Lau 2018.
+// Dual-licensed under either the MIT License or the Boost Software License,
+// Version 1.0.  (See accompanying file LICENSE_1_0.txt or copy at
+// https://www.boost.org/LICENSE_1_0.txt)
+
+//! Choosing an adapter, from a made up list.
+
+extern crate adi_gpu_vulkan;
+
+use adi_gpu_vulkan::{ Adapter, AdapterChoice, AdapterKind, Features };
+
+fn adapter(name: &str, kind: AdapterKind) -> Adapter {
+	Adapter {
+		name: name.to_string(),
+		kind,
+		vendor: 0,
+		device: 0,
+		memory_heaps: vec![1 << 30],
+		features: Features::default(),
+	}
+}
+
+fn laptop() -> Vec<Adapter> {
+	vec![
+		adapter("llvmpipe", AdapterKind::Software),
+		adapter("Intel", AdapterKind::Integrated),
+		adapter("NVIDIA", AdapterKind::Discrete),
+	]
+}
+
+#[test]
+fn select() {
+	let adapters = laptop();
+
+	assert_eq!(AdapterChoice::Default.select(&adapters), Ok(None));
+	assert_eq!(AdapterChoice::Index(1).select(&adapters), Ok(Some(1)));
+	assert!(AdapterChoice::Index(3).select(&adapters).is_err());
+	assert_eq!(AdapterChoice::Prefer(AdapterKind::Discrete)
+		.select(&adapters), Ok(Some(2)));
+	assert_eq!(AdapterChoice::Prefer(AdapterKind::Software)
+		.select(&adapters), Ok(Some(0)));
+	// Nothing virtual, so the driver picks.
+	assert_eq!(AdapterChoice::Prefer(AdapterKind::Virtual)
+		.select(&adapters), Ok(None));
+}
+
+#[test]
+fn parse() {
+	assert_eq!("2".parse(), Ok(AdapterChoice::Index(2)));
+	assert_eq!(" Integrated\n".parse(),
+		Ok(AdapterChoice::Prefer(AdapterKind::Integrated)));
+	assert_eq!("software".parse(),
+		Ok(AdapterChoice::Prefer(AdapterKind::Software)));
+	assert!("fastest".parse::<AdapterChoice>().is_err());
+}
@@ -0,0 +1,376 @@
+// Copyright Jeron A. Lau 2018.
+// Dual-licensed under either the MIT License or the Boost Software License,
+// Version 1.0.  (See accompanying file LICENSE_1_0.txt or copy at
+// https://www.boost.org/LICENSE_1_0.txt)
+
+//! Golden-image tests: render scenes through the headless `Display` and
+//! compare them against the reference rasterizer in `reference`.  They need a
+//! Vulkan implementation (a software one, like lavapipe, works), so they're
+//! ignored unless asked for with `cargo test --test golden -- --ignored`, and
+//! fail if there isn't one.
+
+extern crate adi_gpu_base;
+extern crate adi_gpu_vulkan;
+
+mod reference;
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+use adi_gpu_base::*;
+
+use reference::{ Paint, Raster };
+
+const W: u16 = 64;
+const H: u16 = 64;
+
+/// How far off (out of 255) a channel can be.
+const TOLERANCE: u8 = 3;
+/// How many pixels can be further off, for differences in rasterizing edges.
+const MISMATCHES: usize = (W as usize * H as usize) / 100;
+
+const QUAD: [f32; 16] = [
+	-0.55, -0.55, 0.0, 1.0,
+	0.45, -0.55, 0.0, 1.0,
+	0.45, 0.45, 0.0, 1.0,
+	-0.55, 0.45, 0.0, 1.0,
+];
+const HALF: [f32; 16] = [
+	-0.3, -0.3, 0.0, 1.0,
+	0.2, -0.3, 0.0, 1.0,
+	0.2, 0.2, 0.0, 1.0,
+	-0.3, 0.2, 0.0, 1.0,
+];
+const FANS: [(u32, u32); 1] = [(0, 4)];
+
+/// A scene: the headless display and the reference, kept in step.
+struct Scene {
+	display: Box<adi_gpu_vulkan::Display>,
+	raster: Raster,
+}
+
+impl Scene {
+	fn new(clear: [f32; 3], fog: Option<(f32, f32)>) -> Scene {
+		let mut display = match adi_gpu_vulkan::new_headless(W, H) {
+			Ok(display) => display,
+			Err(e) => {
+				panic!("Golden tests need a Vulkan device: {}",
+					e)
+			}
+		};
+
+		display.color((clear[0], clear[1], clear[2]));
+		display.fog(fog);
+
+		// Same as `Renderer::camera()` with the camera at the origin.
+		let camera = Transform::IDENTITY
+			.m(projection(W as f32 / H as f32, 0.5 * PI).0)
+			.into();
+		let raster = Raster::new(W as usize, H as usize, clear, camera,
+			fog.unwrap_or((::std::f32::MAX, 0.0)));
+
+		Scene { display, raster }
+	}
+
+	/// Render, and compare with the reference.
+	fn check(mut self, name: &str) {
+		self.display.update();
+
+		let actual = self.display.screenshot().unwrap().unwrap().0;
+		let expected = self.raster.rgba();
+
+		compare(name, &actual, &expected);
+	}
+}
+
+fn texels(width: usize, height: usize, rgba: &[u8]) -> reference::Texture {
+	reference::Texture { width, height, rgba: rgba.to_vec() }
+}
+
+fn compare(name: &str, actual: &[u8], expected: &[u8]) {
+	assert_eq!(actual.len(), expected.len());
+
+	let mut diff = vec![0; actual.len()];
+	let mut mismatches = 0;
+
+	for (i, pixel) in actual.chunks(4).enumerate() {
+		let mut off = false;
+
+		// Alpha isn't compared, it depends on the blend factors.
+		for c in 0..3 {
+			let (a, e) = (pixel[c], expected[i * 4 + c]);
+			let d = if a > e { a - e } else { e - a };
+
+			diff[i * 4 + c] = d.saturating_mul(8);
+			off = off || d > TOLERANCE;
+		}
+		diff[i * 4 + 3] = 255;
+
+		if off {
+			mismatches += 1;
+		}
+	}
+
+	if mismatches > MISMATCHES {
+		let dir = write_images(name, actual, expected, &diff);
+
+		panic!("{}: {} pixels differ from the reference, see {}", name,
+			mismatches, dir.display());
+	}
+}
+
+fn write_images(name: &str, actual: &[u8], expected: &[u8], diff: &[u8])
+	-> PathBuf
+{
+	let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+		.join("target/golden");
+
+	fs::create_dir_all(&dir).unwrap();
+
+	for &(suffix, rgba) in [("actual", actual), ("expected", expected),
+		("diff", diff)].iter()
+	{
+		let path = dir.join(format!("{}-{}.ppm", name, suffix));
+		let mut file = fs::File::create(path).unwrap();
+
+		write!(file, "P6\n{} {}\n255\n", W, H).unwrap();
+		for pixel in rgba.chunks(4) {
+			file.write_all(&pixel[..3]).unwrap();
+		}
+	}
+
+	dir
+}
+
+#[test]
+#[ignore]
+fn solid() {
+	let mut scene = Scene::new([0.0, 0.0, 0.0], None);
+	let model = scene.display.model(&QUAD, FANS.to_vec());
+	let transform = Transform::IDENTITY;
+	let shifted = Transform::IDENTITY.t(vec3!(0.3, 0.3, 0.0));
+
+	// GUI shapes are drawn in order.
+	scene.display.shape_solid(&model, transform, [1.0, 0.0, 0.0, 1.0],
+		false, false, false);
+	scene.display.shape_solid(&model, shifted, [0.0, 0.0, 1.0, 0.5],
+		true, false, false);
+
+	scene.raster.draw(&reference::Shape {
+		vertices: &QUAD, fans: &FANS, transform: transform.into(),
+		paint: Paint::Solid([1.0, 0.0, 0.0, 1.0]),
+		blending: false, fog: false, camera: false,
+	});
+	scene.raster.draw(&reference::Shape {
+		vertices: &QUAD, fans: &FANS, transform: shifted.into(),
+		paint: Paint::Solid([0.0, 0.0, 1.0, 0.5]),
+		blending: true, fog: false, camera: false,
+	});
+
+	scene.check("solid");
+}
+
+#[test]
+#[ignore]
+fn gradient() {
+	let mut scene = Scene::new([0.0, 0.0, 0.0], None);
+	let colors = [
+		1.0, 0.0, 0.0, 1.0,
+		0.0, 1.0, 0.0, 1.0,
+		0.0, 0.0, 1.0, 1.0,
+		1.0, 1.0, 1.0, 1.0,
+	];
+	let model = scene.display.model(&QUAD, FANS.to_vec());
+	let gradient = scene.display.gradient(&colors);
+
+	scene.display.shape_gradient(&model, Transform::IDENTITY, gradient,
+		false, false, false);
+	scene.raster.draw(&reference::Shape {
+		vertices: &QUAD, fans: &FANS,
+		transform: Transform::IDENTITY.into(),
+		paint: Paint::Gradient(&colors),
+		blending: false, fog: false, camera: false,
+	});
+
+	scene.check("gradient");
+}
+
+// Left half red, right half blue.  Texture coordinates stay within a quarter
+// of the edge, so nearest and linear filtering give the same result.
+const TEXELS: [u8; 8] = [255, 0, 0, 255, 0, 0, 255, 255];
+const LEFT: [f32; 16] = [
+	0.0, 0.0, 0.0, 1.0,
+	0.25, 0.0, 0.0, 1.0,
+	0.25, 1.0, 0.0, 1.0,
+	0.0, 1.0, 0.0, 1.0,
+];
+const RIGHT: [f32; 16] = [
+	0.75, 0.0, 0.0, 1.0,
+	1.0, 0.0, 0.0, 1.0,
+	1.0, 1.0, 0.0, 1.0,
+	0.75, 1.0, 0.0, 1.0,
+];
+
+#[test]
+#[ignore]
+fn texture() {
+	let mut scene = Scene::new([0.0, 0.0, 0.0], None);
+	let texels = texels(2, 1, &TEXELS);
+	let model = scene.display.model(&HALF, FANS.to_vec());
+	let tx = scene.display.texture((2, 1), &VFrame(TEXELS.to_vec()));
+	let left = Transform::IDENTITY.t(vec3!(-0.4, 0.0, 0.0));
+	let right = Transform::IDENTITY.t(vec3!(0.4, 0.0, 0.0));
+
+	let tc = scene.display.texcoords(&LEFT);
+	scene.display.shape_texture(&model, left, &tx, tc, false, false,
+		false);
+	let tc = scene.display.texcoords(&RIGHT);
+	scene.display.shape_texture(&model, right, &tx, tc, false, false,
+		false);
+
+	scene.raster.draw(&reference::Shape {
+		vertices: &HALF, fans: &FANS, transform: left.into(),
+		paint: Paint::Texture(&texels, &LEFT),
+		blending: false, fog: false, camera: false,
+	});
+	scene.raster.draw(&reference::Shape {
+		vertices: &HALF, fans: &FANS, transform: right.into(),
+		paint: Paint::Texture(&texels, &RIGHT),
+		blending: false, fog: false, camera: false,
+	});
+
+	scene.check("texture");
+}
+
+#[test]
+#[ignore]
+fn faded() {
+	let mut scene = Scene::new([0.0, 1.0, 0.0], None);
+	let texels = texels(2, 1, &TEXELS);
+	let model = scene.display.model(&QUAD, FANS.to_vec());
+	let tx = scene.display.texture((2, 1), &VFrame(TEXELS.to_vec()));
+	let tc = scene.display.texcoords(&LEFT);
+
+	scene.display.shape_faded(&model, Transform::IDENTITY, &tx, tc, 0.5,
+		false, false);
+	scene.raster.draw(&reference::Shape {
+		vertices: &QUAD, fans: &FANS,
+		transform: Transform::IDENTITY.into(),
+		paint: Paint::Faded(&texels, &LEFT, 0.5),
+		blending: true, fog: false, camera: false,
+	});
+
+	scene.check("faded");
+}
+
+#[test]
+#[ignore]
+fn tinted() {
+	let mut scene = Scene::new([0.0, 0.0, 0.0], None);
+	let texels = texels(2, 1, &TEXELS);
+	let model = scene.display.model(&QUAD, FANS.to_vec());
+	let tx = scene.display.texture((2, 1), &VFrame(TEXELS.to_vec()));
+	let tc = scene.display.texcoords(&RIGHT);
+	let tint = [0.5, 1.0, 1.0, 1.0];
+
+	scene.display.shape_tinted(&model, Transform::IDENTITY, &tx, tc, tint,
+		false, false, false);
+	scene.raster.draw(&reference::Shape {
+		vertices: &QUAD, fans: &FANS,
+		transform: Transform::IDENTITY.into(),
+		paint: Paint::Tinted(&texels, &RIGHT, tint),
+		blending: false, fog: false, camera: false,
+	});
+
+	scene.check("tinted");
+}
+
+#[test]
+#[ignore]
+fn tinted_blended() {
+	let mut scene = Scene::new([0.0, 0.0, 0.0], None);
+	let texels = texels(2, 1, &TEXELS);
+	let model = scene.display.model(&QUAD, FANS.to_vec());
+	let tx = scene.display.texture((2, 1), &VFrame(TEXELS.to_vec()));
+	let tc = scene.display.texcoords(&RIGHT);
+	let clear = [1.0, 1.0, 1.0, 0.0];
+	let shifted = Transform::IDENTITY.t(vec3!(0.3, 0.3, 0.0));
+
+	// The blended style discards the invisible shape, so it doesn't hide
+	// the one drawn after it at the same depth.
+	scene.display.shape_tinted(&model, Transform::IDENTITY, &tx, tc, clear,
+		true, false, false);
+	scene.display.shape_solid(&model, shifted, [1.0, 0.0, 0.0, 1.0],
+		false, false, false);
+	scene.raster.draw(&reference::Shape {
+		vertices: &QUAD, fans: &FANS,
+		transform: Transform::IDENTITY.into(),
+		paint: Paint::Tinted(&texels, &RIGHT, clear),
+		blending: true, fog: false, camera: false,
+	});
+	scene.raster.draw(&reference::Shape {
+		vertices: &QUAD, fans: &FANS, transform: shifted.into(),
+		paint: Paint::Solid([1.0, 0.0, 0.0, 1.0]),
+		blending: false, fog: false, camera: false,
+	});
+
+	scene.check("tinted_blended");
+}
+
+#[test]
+#[ignore]
+fn complex() {
+	let mut scene = Scene::new([0.0, 0.0, 0.0], None);
+	let colors = [
+		1.0, 1.0, 1.0, 1.0,
+		0.0, 1.0, 1.0, 1.0,
+		1.0, 0.0, 1.0, 1.0,
+		1.0, 1.0, 0.0, 1.0,
+	];
+	let white = [255; 4];
+	let texels = texels(1, 1, &white);
+	let model = scene.display.model(&QUAD, FANS.to_vec());
+	let tx = scene.display.texture((1, 1), &VFrame(white.to_vec()));
+	let tc = scene.display.texcoords(&LEFT);
+	let gradient = scene.display.gradient(&colors);
+
+	scene.display.shape_complex(&model, Transform::IDENTITY, &tx, tc,
+		gradient, false, false, false);
+	scene.raster.draw(&reference::Shape {
+		vertices: &QUAD, fans: &FANS,
+		transform: Transform::IDENTITY.into(),
+		paint: Paint::Complex(&texels, &LEFT, &colors),
+		blending: false, fog: false, camera: false,
+	});
+
+	scene.check("complex");
+}
+
+#[test]
+#[ignore]
+fn fog() {
+	let mut scene = Scene::new([0.5, 0.5, 0.5], Some((1.0, 4.0)));
+	let model = scene.display.model(&QUAD, FANS.to_vec());
+	let near = Transform::IDENTITY.t(vec3!(-0.5, 0.0, 1.5));
+	let far = Transform::IDENTITY.t(vec3!(0.5, 0.0, 3.5));
+
+	scene.display.shape_solid(&model, near, [1.0, 0.0, 0.0, 1.0], false,
+		true, true);
+	scene.display.shape_solid(&model, far, [0.0, 0.0, 1.0, 1.0], false,
+		true, true);
+
+	// Opaque shapes are drawn nearest first.
+	for &(transform, color) in [(near, [1.0, 0.0, 0.0, 1.0]),
+		(far, [0.0, 0.0, 1.0, 1.0])].iter()
+	{
+		scene.raster.draw(&reference::Shape {
+			vertices: &QUAD, fans: &FANS,
+			transform: transform.into(),
+			paint: Paint::Solid(color),
+			blending: false, fog: true, camera: true,
+		});
+	}
+
+	scene.check("fog");
+}
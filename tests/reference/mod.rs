@@ -0,0 +1,322 @@
+// Copyright Jeron A. Lau 2018.
+// Dual-licensed under either the MIT License or the Boost Software License,
+// Version 1.0.  (See accompanying file LICENSE_1_0.txt or copy at
+// https://www.boost.org/LICENSE_1_0.txt)
+
+//! A small CPU rasterizer with the same semantics as the shaders in
+//! `src/shaders/glsl`: triangle fans, `has_camera`, cubic fog and alpha
+//! blending (with the `bfrag` discard of transparent fragments).  Textures
+//! are sampled nearest with clamping, so tests should only sample away from
+//! texel edges.
+
+pub type Rgba = [f32; 4];
+
+/// A texture, as RGBA bytes.
+pub struct Texture {
+	pub width: usize,
+	pub height: usize,
+	pub rgba: Vec<u8>,
+}
+
+impl Texture {
+	fn sample(&self, u: f32, v: f32) -> Rgba {
+		let x = clamp((u * self.width as f32).floor(), 0.0,
+			self.width as f32 - 1.0) as usize;
+		let y = clamp((v * self.height as f32).floor(), 0.0,
+			self.height as f32 - 1.0) as usize;
+		let i = (y * self.width + x) * 4;
+
+		[
+			self.rgba[i] as f32 / 255.0,
+			self.rgba[i + 1] as f32 / 255.0,
+			self.rgba[i + 2] as f32 / 255.0,
+			self.rgba[i + 3] as f32 / 255.0,
+		]
+	}
+}
+
+/// What the fragment shader does, one variant per `Style`.
+pub enum Paint<'a> {
+	Solid(Rgba),
+	Gradient(&'a [f32]),
+	Texture(&'a Texture, &'a [f32]),
+	Faded(&'a Texture, &'a [f32], f32),
+	Tinted(&'a Texture, &'a [f32], Rgba),
+	Complex(&'a Texture, &'a [f32], &'a [f32]),
+}
+
+/// A shape, as passed to `Display::shape_*()`.
+pub struct Shape<'a> {
+	pub vertices: &'a [f32],
+	pub fans: &'a [(u32, u32)],
+	pub transform: [f32; 16],
+	pub paint: Paint<'a>,
+	pub blending: bool,
+	pub fog: bool,
+	pub camera: bool,
+}
+
+/// Output of the vertex shader.
+#[derive(Copy, Clone)]
+struct Vertex {
+	position: Rgba,
+	z: f32,
+	color: Rgba,
+	texcoord: Rgba,
+}
+
+/// A color and depth buffer to draw `Shape`s into.
+pub struct Raster {
+	width: usize,
+	height: usize,
+	color: Vec<Rgba>,
+	depth: Vec<f32>,
+	camera: [f32; 16],
+	fog_color: Rgba,
+	fog_range: [f32; 2],
+}
+
+fn clamp(x: f32, min: f32, max: f32) -> f32 {
+	x.max(min).min(max)
+}
+
+fn vec4(data: &[f32], i: usize) -> Rgba {
+	[data[i * 4], data[i * 4 + 1], data[i * 4 + 2], data[i * 4 + 3]]
+}
+
+// Column-major, like GLSL.
+fn mul(m: &[f32; 16], v: Rgba) -> Rgba {
+	let mut out = [0.0; 4];
+
+	for row in 0..4 {
+		for col in 0..4 {
+			out[row] += m[col * 4 + row] * v[col];
+		}
+	}
+
+	out
+}
+
+fn mix(a: Rgba, b: Rgba, t: f32) -> Rgba {
+	[
+		a[0] + (b[0] - a[0]) * t,
+		a[1] + (b[1] - a[1]) * t,
+		a[2] + (b[2] - a[2]) * t,
+		a[3] + (b[3] - a[3]) * t,
+	]
+}
+
+fn edge(a: (f32, f32), b: (f32, f32), p: (f32, f32)) -> f32 {
+	(b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0)
+}
+
+impl Raster {
+	/// Create a raster cleared to `clear`, with the camera matrix and fog
+	/// range that the renderer would upload.
+	pub fn new(width: usize, height: usize, clear: [f32; 3],
+		camera: [f32; 16], fog: (f32, f32)) -> Raster
+	{
+		let fog_color = [clear[0], clear[1], clear[2], 1.0];
+
+		Raster {
+			width, height,
+			color: vec![fog_color; width * height],
+			depth: vec![1.0; width * height],
+			camera, fog_color,
+			fog_range: [fog.0, fog.1],
+		}
+	}
+
+	/// Draw a shape.  Shapes must be drawn in the order the renderer draws
+	/// them: opaque nearest first, then alpha farthest first, then GUI.
+	pub fn draw(&mut self, shape: &Shape) {
+		let hcam = shape.fog as u32 + shape.camera as u32;
+		let count = shape.vertices.len() / 4;
+		let mut vertices = Vec::with_capacity(count);
+
+		for i in 0..count {
+			let pos = vec4(shape.vertices, i);
+			let place = mul(&shape.transform,
+				[pos[0], pos[1], pos[2], 1.0]);
+			let position = if hcam >= 1 {
+				mul(&self.camera, place)
+			} else {
+				place
+			};
+			let z = (position[0] * position[0]
+				+ position[1] * position[1]
+				+ position[2] * position[2]).sqrt();
+			let none = [0.0; 4];
+
+			let (color, texcoord) = match shape.paint {
+				Paint::Solid(color) => (color, none),
+				Paint::Gradient(colors) => (vec4(colors, i), none),
+				Paint::Texture(_, tc) => (none, vec4(tc, i)),
+				Paint::Faded(_, tc, alpha) => {
+					let mut tc = vec4(tc, i);
+					tc[3] *= alpha;
+					(none, tc)
+				}
+				Paint::Tinted(_, tc, tint) => (tint, vec4(tc, i)),
+				Paint::Complex(_, tc, colors) => {
+					(vec4(colors, i), vec4(tc, i))
+				}
+			};
+
+			vertices.push(Vertex { position, z, color, texcoord });
+		}
+
+		for &(first, count) in shape.fans {
+			let first = first as usize;
+
+			for i in 1..(count as usize).saturating_sub(1) {
+				self.triangle(shape, hcam, [
+					vertices[first],
+					vertices[first + i],
+					vertices[first + i + 1],
+				]);
+			}
+		}
+	}
+
+	fn triangle(&mut self, shape: &Shape, hcam: u32, v: [Vertex; 3]) {
+		// No clipping, tests keep everything in front of the camera.
+		if v.iter().any(|v| v.position[3] <= 0.0) {
+			return;
+		}
+
+		let (w, h) = (self.width as f32, self.height as f32);
+		let screen: Vec<(f32, f32)> = v.iter().map(|v| {
+			let p = v.position;
+			((p[0] / p[3] * 0.5 + 0.5) * w,
+				(p[1] / p[3] * 0.5 + 0.5) * h)
+		}).collect();
+		let area = edge(screen[0], screen[1], screen[2]);
+
+		if area == 0.0 {
+			return;
+		}
+
+		let min_x = screen.iter().fold(w, |m, p| m.min(p.0)).max(0.0);
+		let max_x = screen.iter().fold(0.0f32, |m, p| m.max(p.0)).min(w);
+		let min_y = screen.iter().fold(h, |m, p| m.min(p.1)).max(0.0);
+		let max_y = screen.iter().fold(0.0f32, |m, p| m.max(p.1)).min(h);
+
+		for y in (min_y as usize)..(max_y.ceil() as usize) {
+			for x in (min_x as usize)..(max_x.ceil() as usize) {
+				let p = (x as f32 + 0.5, y as f32 + 0.5);
+				let l = [
+					edge(screen[1], screen[2], p) / area,
+					edge(screen[2], screen[0], p) / area,
+					edge(screen[0], screen[1], p) / area,
+				];
+
+				if l.iter().any(|l| *l < 0.0) {
+					continue;
+				}
+
+				self.fragment(shape, hcam, &v, l, x, y);
+			}
+		}
+	}
+
+	fn fragment(&mut self, shape: &Shape, hcam: u32, v: &[Vertex; 3],
+		l: [f32; 3], x: usize, y: usize)
+	{
+		// Depth is linear in screen space.
+		let depth = (0..3).fold(0.0, |d, i| {
+			d + l[i] * v[i].position[2] / v[i].position[3]
+		});
+
+		if depth < 0.0 || depth > 1.0
+			|| depth >= self.depth[y * self.width + x]
+		{
+			return;
+		}
+
+		// Everything else is perspective correct.
+		let pl: Vec<f32> = (0..3).map(|i| l[i] / v[i].position[3])
+			.collect();
+		let sum = pl[0] + pl[1] + pl[2];
+		let lerp = |f: &dyn Fn(&Vertex) -> f32| {
+			(0..3).fold(0.0, |a, i| a + pl[i] * f(&v[i])) / sum
+		};
+		let z = lerp(&|v| v.z);
+		let color = [
+			lerp(&|v| v.color[0]), lerp(&|v| v.color[1]),
+			lerp(&|v| v.color[2]), lerp(&|v| v.color[3]),
+		];
+		let tc = [
+			lerp(&|v| v.texcoord[0]), lerp(&|v| v.texcoord[1]),
+			lerp(&|v| v.texcoord[2]), lerp(&|v| v.texcoord[3]),
+		];
+
+		let textured = |texture: &Texture| {
+			let s = texture.sample(tc[0], tc[1]);
+			[s[0], s[1], s[2], s[3] * tc[3]]
+		};
+
+		let out = match shape.paint {
+			Paint::Solid(_) | Paint::Gradient(_) => color,
+			Paint::Texture(texture, _)
+				| Paint::Faded(texture, _, _) => textured(texture),
+			Paint::Tinted(texture, _, _)
+				| Paint::Complex(texture, _, _) =>
+			{
+				let s = textured(texture);
+				[s[0] * color[0], s[1] * color[1],
+					s[2] * color[2], s[3] * color[3]]
+			}
+		};
+
+		let blending = match shape.paint {
+			Paint::Faded(..) => true,
+			_ => shape.blending,
+		};
+
+		// Blended styles discard fully transparent fragments.
+		if blending && out[3] <= 0.0 {
+			return;
+		}
+
+		let out = if hcam == 2 {
+			// Fog Calculation
+			let linear = clamp((z - self.fog_range[0])
+				/ self.fog_range[1], 0.0, 1.0);
+			let curved = linear * linear * linear;
+			mix(out, self.fog_color, curved)
+		} else {
+			out
+		};
+
+		let i = y * self.width + x;
+
+		self.color[i] = if blending {
+			let a = out[3];
+			let d = self.color[i];
+			[
+				out[0] * a + d[0] * (1.0 - a),
+				out[1] * a + d[1] * (1.0 - a),
+				out[2] * a + d[2] * (1.0 - a),
+				a + d[3] * (1.0 - a),
+			]
+		} else {
+			out
+		};
+		self.depth[i] = depth;
+	}
+
+	/// Get the raster as RGBA bytes, like `Display::screenshot()`.
+	pub fn rgba(&self) -> Vec<u8> {
+		let mut rgba = Vec::with_capacity(self.color.len() * 4);
+
+		for pixel in self.color.iter() {
+			for channel in pixel.iter() {
+				rgba.push((clamp(*channel, 0.0, 1.0) * 255.0)
+					.round() as u8);
+			}
+		}
+
+		rgba
+	}
+}
@@ -0,0 +1,1264 @@
+// Copyright Jeron A. Lau 2018.
+// Dual-licensed under either the MIT License or the Boost Software License,
+// Version 1.0.  (See accompanying file LICENSE_1_0.txt or copy at
+// https://www.boost.org/LICENSE_1_0.txt)
+
+//! Renderer tests that don't need a GPU: they check the calls made on a
+//! `Recorder`.
+
+extern crate adi_gpu_base;
+extern crate adi_gpu_vulkan;
+extern crate asi_vulkan;
+
+use adi_gpu_base::*;
+use asi_vulkan::types::{ VkFormat, VkSampleCount };
+
+use adi_gpu_vulkan::{ Call, DebugMode, DisplayConfig, Error, HandleError };
+use adi_gpu_vulkan::{ ColorSpace, Filter, PresentMode, Recorder };
+use adi_gpu_vulkan::SamplerDesc;
+use adi_gpu_vulkan::{ TextureConfig, TextureFormat };
+
+const QUAD: [f32; 16] = [
+	-0.5, -0.5, 0.0, 1.0,
+	0.5, -0.5, 0.0, 1.0,
+	0.5, 0.5, 0.0, 1.0,
+	-0.5, 0.5, 0.0, 1.0,
+];
+const FANS: [(u32, u32); 1] = [(0, 4)];
+
+/// Make a display, whose textures are `staged` into optimal tiled images.
+fn display(staged: bool) -> Box<adi_gpu_vulkan::Display<Recorder>> {
+	let recorder = if staged {
+		Recorder::new().staged()
+	} else {
+		Recorder::new()
+	};
+
+	adi_gpu_vulkan::with_device(recorder, (64, 64),
+		DisplayConfig::default()).unwrap()
+}
+
+/// Get the id of the last sprite made.
+fn last_sprite(calls: &[Call]) -> u32 {
+	calls.iter().rev().filter_map(|call| match *call {
+		Call::Sprite { sprite, .. } => Some(sprite),
+		_ => None,
+	}).next().unwrap()
+}
+
+/// Get the ids of the sprites made for the last shape, one for each of the
+/// 2 frames in flight, in order.
+fn last_sprites(calls: &[Call]) -> Vec<u32> {
+	let mut sprites = calls.iter().rev().filter_map(|call| match *call {
+		Call::Sprite { sprite, .. } => Some(sprite),
+		_ => None,
+	}).take(2).collect::<Vec<_>>();
+
+	sprites.reverse();
+	sprites
+}
+
+/// Get the id and format of the last image made.
+fn last_image(calls: &[Call]) -> (u32, VkFormat) {
+	calls.iter().rev().filter_map(|call| match *call {
+		Call::Image { image, format, .. } => Some((image, format)),
+		_ => None,
+	}).next().unwrap()
+}
+
+/// Get the ids of the sprites drawn in the last frame, in order.
+fn drawn(calls: &[Call]) -> Vec<u32> {
+	let begin = calls.iter().rposition(|call| match *call {
+		Call::DrawBegin { .. } => true,
+		_ => false,
+	}).unwrap();
+
+	calls[begin..].iter().filter_map(|call| match *call {
+		Call::BindSprite(sprite) => Some(sprite),
+		_ => None,
+	}).collect()
+}
+
+/// Check whether any of `calls` writes the uniform of `sprite`.
+fn written(calls: &[Call], sprite: u32) -> bool {
+	calls.iter().any(|call| match *call {
+		Call::SpriteWrite { sprite: s, .. } => s == sprite,
+		_ => false,
+	})
+}
+
+#[test]
+fn shapes() {
+	let mut display = display(false);
+	let model = display.model(&QUAD, FANS.to_vec());
+	let shape = display.shape_solid(&model, Transform::IDENTITY,
+		[1.0, 1.0, 1.0, 1.0], false, true, true);
+	let sprites = last_sprites(display.device().calls());
+	assert_eq!(sprites.len(), 2);
+
+	// Every fan of the model is drawn, then the frame is presented.
+	display.device_mut().clear();
+	display.update();
+	let calls = display.device().calls();
+	assert_eq!(drawn(calls), vec![sprites[0]]);
+	assert!(calls.contains(&Call::Draw {
+		vertex_count: 4, first_vertex: 0
+	}));
+	assert!(match calls.last() {
+		Some(&Call::QueuePresent(_)) => true,
+		_ => false,
+	});
+
+	// Moving a shape writes its uniform, before the next frame draws it.
+	display.device_mut().clear();
+	display.transform(&shape, Transform::IDENTITY.t(vec3!(0.5, 0.0, 0.0)));
+	display.update();
+	assert!(written(display.device().calls(), sprites[1]));
+	assert_eq!(drawn(display.device().calls()), vec![sprites[1]]);
+
+	// A dropped shape isn't drawn or moved, and can't be dropped twice.
+	display.device_mut().clear();
+	display.drop_shape(&shape);
+	display.drop_shape(&shape);
+	display.transform(&shape, Transform::IDENTITY);
+	display.update();
+	let calls = display.device().calls();
+	assert!(!written(calls, sprites[0]));
+	assert_eq!(drawn(calls), vec![]);
+
+	// Resizing waits for the frames in flight, so the sprites are freed
+	// (once) by now, and remakes the swapchain after dropping the old one.
+	display.resize((32, 16));
+	let calls = display.device().calls();
+	for &sprite in sprites.iter() {
+		assert_eq!(calls.iter().filter(|call| {
+			**call == Call::SpriteDrop(sprite)
+		}).count(), 1);
+	}
+	let drop = calls.iter().position(|call| match *call {
+		Call::SwapchainDrop { .. } => true,
+		_ => false,
+	}).unwrap();
+	let new = calls.iter().position(|call| match *call {
+		Call::SwapchainNew { .. } => true,
+		_ => false,
+	}).unwrap();
+	assert!(drop < new);
+	assert_eq!(display.wh(), (32, 16));
+}
+
+#[test]
+fn headless() {
+	let mut display = adi_gpu_vulkan::headless_with_device(Recorder::new(),
+		(16, 8), DisplayConfig::default()).unwrap();
+	let offscreen = |calls: &[Call]| calls.iter().filter_map(|call| {
+		match *call {
+			Call::OffscreenNew { width, height, .. } => {
+				Some((width, height))
+			}
+			Call::SwapchainNew { .. } => panic!("made a swapchain"),
+			_ => None,
+		}
+	}).collect::<Vec<_>>();
+	assert_eq!(offscreen(display.device().calls()), vec![(16, 8)]);
+	assert_eq!(display.image_count(), 1);
+
+	// Frames are submitted, but there's no image to get or present.
+	display.device_mut().clear();
+	display.update();
+	let calls = display.device().calls();
+	assert!(calls.iter().any(|call| match *call {
+		Call::QueueSubmit { .. } => true,
+		_ => false,
+	}));
+	assert!(calls.iter().all(|call| match *call {
+		Call::NextImage(_) | Call::QueuePresent(_) => false,
+		_ => true,
+	}));
+
+	// Resizing makes a new offscreen image.
+	display.device_mut().clear();
+	display.resize((4, 4));
+	let calls = display.device().calls();
+	assert_eq!(offscreen(calls), vec![(4, 4)]);
+	assert!(calls.iter().any(|call| match *call {
+		Call::OffscreenDrop { .. } => true,
+		_ => false,
+	}));
+}
+
+#[test]
+fn screenshot() {
+	let mut display = display(false);
+	assert_eq!(display.screenshot().unwrap().map(|rgba| rgba.0), None);
+
+	display.update();
+	let image = display.device().calls().iter().rev()
+		.filter_map(|call| match *call {
+			Call::DrawBegin { image, .. } => Some(image),
+			_ => None,
+		}).next().unwrap();
+	display.device_mut().clear();
+	let rgba = display.screenshot().unwrap().unwrap();
+	assert_eq!(rgba.0.len(), 64 * 64 * 4);
+
+	// The frame is finished before the image it drew is read back, and
+	// the image it's read into is freed.
+	let calls = display.device().calls();
+	let wait = calls.iter().position(|call| match *call {
+		Call::FenceWait(_) => true,
+		_ => false,
+	}).unwrap();
+	let (read, dst) = calls.iter().enumerate().filter_map(|(i, call)| {
+		match *call {
+			Call::ImageReadBack { src, dst } if src == image => {
+				Some((i, dst))
+			}
+			_ => None,
+		}
+	}).next().unwrap();
+	assert!(wait < read);
+	assert!(calls[read..].contains(&Call::ImageDrop(dst)));
+
+	// The new swapchain's images haven't been rendered into.
+	display.resize((32, 32));
+	assert_eq!(display.screenshot().unwrap().map(|rgba| rgba.0), None);
+	display.update();
+	let rgba = display.screenshot().unwrap().unwrap();
+	assert_eq!(rgba.0.len(), 32 * 32 * 4);
+}
+
+/// Get the fences waited for in `calls`.
+fn waits(calls: &[Call]) -> Vec<u32> {
+	calls.iter().filter_map(|call| match *call {
+		Call::FenceWait(fence) => Some(fence),
+		_ => None,
+	}).collect()
+}
+
+#[test]
+fn frames_in_flight() {
+	let mut display = display(false);
+	let model = display.model(&QUAD, FANS.to_vec());
+	let shape = display.shape_solid(&model, Transform::IDENTITY,
+		[1.0, 1.0, 1.0, 1.0], false, true, true);
+	let sprites = last_sprites(display.device().calls());
+
+	// The second frame doesn't wait for the first, but the third waits
+	// for the first to be done with its command buffer.
+	display.device_mut().clear();
+	display.update();
+	display.update();
+	let fences = display.device().calls().iter().filter_map(|call| {
+		match *call {
+			Call::QueueSubmit { fence, .. } => Some(fence),
+			_ => None,
+		}
+	}).collect::<Vec<_>>();
+	assert_eq!(fences.len(), 2);
+	assert_ne!(fences[0], fences[1]);
+	assert_eq!(waits(display.device().calls()), vec![]);
+	display.update();
+	assert_eq!(waits(display.device().calls()), vec![fences[0]]);
+
+	// Moving a shape or the camera doesn't wait at all.  Each frame has
+	// its own uniforms and camera, written once only that frame is done.
+	display.device_mut().clear();
+	display.transform(&shape, Transform::IDENTITY.t(vec3!(0.5, 0.0, 0.0)));
+	display.camera(vec3!(0.0, 0.0, 1.0), vec3!(0.0, 0.0, 0.0));
+	assert_eq!(display.device().calls(), &[][..]);
+	let writes = |calls: &[Call]| calls.iter().position(|call| {
+		match *call {
+			Call::SpriteWrite { .. } => true,
+			Call::CameraWrite(_) => true,
+			_ => false,
+		}
+	}).unwrap();
+	display.update();
+	let calls = display.device().calls();
+	assert_eq!(waits(calls), vec![fences[1]]);
+	assert_eq!(waits(&calls[..writes(calls)]), vec![fences[1]]);
+	assert!(written(calls, sprites[1]));
+	assert!(!written(calls, sprites[0]));
+	display.device_mut().clear();
+	display.update();
+	let calls = display.device().calls();
+	assert_eq!(waits(&calls[..writes(calls)]), vec![fences[0]]);
+	assert!(written(calls, sprites[0]));
+	assert!(!written(calls, sprites[1]));
+
+	// Writing a texture only waits for the frames in flight that drew it.
+	let mut texture = display.texture((1, 1), &VFrame(vec![255; 4]));
+	display.device_mut().clear();
+	display.set_texture(&mut texture, (1, 1), &VFrame(vec![0; 4]));
+	assert_eq!(waits(display.device().calls()), vec![]);
+	let tc = display.texcoords(&QUAD);
+	display.shape_texture(&model, Transform::IDENTITY, &texture, tc,
+		false, false, false);
+	display.update();
+	display.update();
+	display.device_mut().clear();
+	display.set_texture(&mut texture, (1, 1), &VFrame(vec![0; 4]));
+	assert_eq!(waits(display.device().calls()), fences);
+
+	// A dropped shape's sprites are freed once the last frame that drew
+	// them is done, without waiting for it early.
+	display.update();
+	display.drop_shape(&shape);
+	display.device_mut().clear();
+	display.update();
+	assert!(!display.device().calls().contains(&Call::SpriteDrop(
+		sprites[0])));
+	display.update();
+	let calls = display.device().calls();
+	let wait = calls.iter().position(|call| match *call {
+		Call::FenceWait(_) => true,
+		_ => false,
+	}).unwrap();
+	for &sprite in sprites.iter() {
+		let drop = calls.iter().position(|call| {
+			*call == Call::SpriteDrop(sprite)
+		}).unwrap();
+		assert!(wait < drop);
+	}
+}
+
+#[test]
+fn swapchain_fallbacks() {
+	let select = |modes: &[PresentMode], (min, max), wanted, count| {
+		let config = DisplayConfig {
+			present_mode: wanted,
+			image_count: count,
+			.. DisplayConfig::default()
+		};
+		let display = adi_gpu_vulkan::with_device(Recorder::new()
+			.surface(modes.to_vec(), min, max), (64, 64), config)
+			.unwrap();
+		let made = display.device().calls().iter().filter_map(|call| {
+			match *call {
+				Call::SwapchainNew { present_mode, image_count,
+					.. } =>
+				{
+					Some((present_mode, image_count))
+				}
+				_ => None,
+			}
+		}).next().unwrap();
+
+		assert_eq!(made, (display.present_mode(),
+			display.image_count()));
+		made
+	};
+	let fifo = [PresentMode::Fifo];
+	let mailbox = [PresentMode::Fifo, PresentMode::Mailbox];
+	let immediate = [PresentMode::Fifo, PresentMode::Immediate];
+
+	// Immediate falls back to mailbox, then FIFO, and mailbox to FIFO.
+	assert_eq!(select(&immediate, (2, 0), PresentMode::Immediate, 2).0,
+		PresentMode::Immediate);
+	assert_eq!(select(&mailbox, (2, 0), PresentMode::Immediate, 2).0,
+		PresentMode::Mailbox);
+	assert_eq!(select(&fifo, (2, 0), PresentMode::Immediate, 2).0,
+		PresentMode::Fifo);
+	assert_eq!(select(&immediate, (2, 0), PresentMode::Mailbox, 2).0,
+		PresentMode::Fifo);
+
+	// Image counts are 2 to 4, then what the surface supports.
+	assert_eq!(select(&fifo, (1, 0), PresentMode::Fifo, 0).1, 2);
+	assert_eq!(select(&fifo, (1, 0), PresentMode::Fifo, 9).1, 4);
+	assert_eq!(select(&fifo, (3, 0), PresentMode::Fifo, 2).1, 3);
+	assert_eq!(select(&fifo, (2, 3), PresentMode::Fifo, 4).1, 3);
+	assert_eq!(select(&fifo, (5, 6), PresentMode::Fifo, 4).1, 5);
+}
+
+#[test]
+fn drops_wait_for_shapes_and_frames() {
+	let mut display = display(false);
+	let model = display.model(&QUAD, FANS.to_vec());
+	let texture = display.texture((1, 1), &VFrame(vec![255; 4]));
+	let tc = display.texcoords(&QUAD);
+	let colors = display.gradient(&[1.0; 16]);
+	let shape = display.shape_complex(&model, Transform::IDENTITY,
+		&texture, tc, colors, false, false, false);
+	let buffers = display.device().calls().iter().filter_map(|call| {
+		match *call {
+			Call::Buffer { buffer, .. } => Some(buffer),
+			_ => None,
+		}
+	}).collect::<Vec<_>>();
+	assert_eq!(buffers.len(), 3);
+
+	// Nothing a shape is made from can be dropped before the shape.
+	display.update();
+	assert_eq!(display.drop_model(&model), Err(HandleError::InUse));
+	assert_eq!(display.drop_texture(&texture), Err(HandleError::InUse));
+	assert_eq!(display.drop_texcoords(&tc), Err(HandleError::InUse));
+	assert_eq!(display.drop_gradient(&colors), Err(HandleError::InUse));
+	display.update();
+	assert_eq!(display.check_shape(&shape), Ok(()));
+	assert_eq!(drawn(display.device().calls()).len(), 1);
+	let last = display.device().calls().iter().rev().filter_map(|call| {
+		match *call {
+			Call::QueueSubmit { fence, .. } => Some(fence),
+			_ => None,
+		}
+	}).next().unwrap();
+
+	// Once it's dropped they can be, but they're only freed once the
+	// frame that last drew them is done.
+	display.drop_shape(&shape);
+	display.device_mut().clear();
+	assert_eq!(display.drop_model(&model), Ok(()));
+	assert_eq!(display.drop_texture(&texture), Ok(()));
+	assert_eq!(display.drop_texcoords(&tc), Ok(()));
+	assert_eq!(display.drop_gradient(&colors), Ok(()));
+	assert_eq!(display.drop_model(&model), Err(HandleError::Stale));
+	let freed = |calls: &[Call]| calls.iter().filter(|call| match **call {
+		Call::BufferDrop(buffer) => buffers.contains(&buffer),
+		Call::ImageDrop(_) => true,
+		_ => false,
+	}).count();
+	assert_eq!(freed(display.device().calls()), 0);
+	display.update();
+	assert_eq!(freed(display.device().calls()), 0);
+	display.device_mut().clear();
+	display.update();
+	let calls = display.device().calls();
+	let wait = calls.iter().position(|call| *call == Call::FenceWait(last))
+		.unwrap();
+	assert_eq!(freed(&calls[..wait]), 0);
+	assert_eq!(freed(calls), 4);
+
+	// And making everything again on a new device skips them.
+	display.device_mut().clear();
+	display.device_mut().fail(Error::DeviceLost);
+	display.update();
+	assert!(display.device_lost());
+	assert!(display.device().calls().contains(&Call::DeviceReset));
+	assert!(display.device().calls().iter().all(|call| match *call {
+		Call::Buffer { .. } => false,
+		Call::Image { .. } => false,
+		_ => true,
+	}));
+}
+
+#[test]
+fn stale_handles() {
+	let mut display = display(false);
+	let first = display.model(&QUAD, FANS.to_vec());
+	let shape = display.shape_solid(&first, Transform::IDENTITY,
+		[1.0, 1.0, 1.0, 1.0], false, false, false);
+	display.drop_shape(&shape);
+	display.drop_model(&first).unwrap();
+	let mut models = std::collections::HashSet::new();
+
+	// Slots are reused more times than there are generations, but an old
+	// handle never refers to a new object.
+	for _ in 0..1000 {
+		let model = display.model(&QUAD, FANS.to_vec());
+		let new = display.shape_solid(&model, Transform::IDENTITY,
+			[1.0, 1.0, 1.0, 1.0], false, false, false);
+		assert!(models.insert(model.0));
+		assert_eq!(display.check_shape(&shape),
+			Err(HandleError::Stale));
+		assert_eq!(display.drop_model(&first),
+			Err(HandleError::Stale));
+
+		display.drop_shape(&new);
+		display.drop_model(&model).unwrap();
+		assert_eq!(display.drop_model(&model), Err(HandleError::Stale));
+	}
+
+	// A handle for one kind of object isn't one for another, and 0 is
+	// never a handle.
+	let texture = display.texture((1, 1), &VFrame(vec![255; 4]));
+	assert_eq!(display.drop_model(&Model(texture.0)),
+		Err(HandleError::Foreign));
+	assert_eq!(display.drop_model(&Model(0)), Err(HandleError::Foreign));
+}
+
+#[test]
+fn dropped_shapes_free_sprites() {
+	let mut display = display(false);
+	let model = display.model(&QUAD, FANS.to_vec());
+	let mut shape = |blending, camera| {
+		let shape = display.shape_solid(&model, Transform::IDENTITY,
+			[1.0, 1.0, 1.0, 0.5], blending, false, camera);
+
+		(shape, last_sprites(display.device().calls()))
+	};
+	// An opaque, alpha and GUI shape.
+	let shapes = [shape(false, true), shape(true, true),
+		shape(false, false)];
+	let drops = |calls: &[Call], sprite| calls.iter().filter(|call| {
+		**call == Call::SpriteDrop(sprite)
+	}).count();
+
+	display.update();
+	display.device_mut().clear();
+	for &(ref shape, _) in shapes.iter() {
+		display.drop_shape(shape);
+		display.drop_shape(shape);
+	}
+	display.update();
+	display.update();
+	display.update();
+
+	// Each sprite is freed once, after the frame that drew it is done.
+	let calls = display.device().calls();
+	let wait = calls.iter().position(|call| match *call {
+		Call::FenceWait(_) => true,
+		_ => false,
+	}).unwrap();
+	for &(_, ref sprites) in shapes.iter() {
+		for &sprite in sprites.iter() {
+			assert_eq!(drops(&calls[..wait], sprite), 0);
+			assert_eq!(drops(calls, sprite), 1);
+		}
+	}
+}
+
+#[test]
+fn gui_layers() {
+	let mut display = display(false);
+	let model = display.model(&QUAD, FANS.to_vec());
+	let mut gui = || {
+		let shape = display.shape_solid(&model, Transform::IDENTITY,
+			[1.0, 1.0, 1.0, 1.0], false, false, false);
+
+		(shape, last_sprites(display.device().calls())[0])
+	};
+	let (top, top_sprite) = gui();
+	let (dropped, _) = gui();
+	let (bottom, bottom_sprite) = gui();
+
+	display.gui_layer(&top, 1).unwrap();
+	display.drop_shape(&dropped);
+
+	assert_eq!(display.check_shape(&dropped), Err(HandleError::Stale));
+	assert_eq!(display.check_shape(&bottom), Ok(()));
+
+	display.update();
+	assert_eq!(drawn(display.device().calls()),
+		vec![bottom_sprite, top_sprite]);
+}
+
+#[test]
+fn custom_style_checks_layout() {
+	let mut display = display(false);
+	let vert = include_bytes!("../src/shaders/res/texture-vert.spv");
+	let frag = include_bytes!("../src/shaders/res/texture-frag.spv");
+
+	// The texture shaders don't have room for a custom uniform.
+	assert!(display.style::<[f32; 4]>(vert, frag, 1, 2).is_err());
+	// Or the wrong number of textures and vertex buffers.
+	assert!(display.style::<()>(vert, frag, 0, 2).is_err());
+	assert!(display.style::<()>(vert, frag, 1, 3).is_err());
+	// Or things that aren't SPIR-V.
+	assert!(display.style::<()>(&[0; 64], frag, 1, 2).is_err());
+}
+
+/// Assemble a SPIR-V module from (opcode, operands) pairs.
+fn spirv(instructions: &[(u32, &[u32])]) -> Vec<u8> {
+	let mut words = vec![0x07230203, 0x00010000, 0, 64, 0];
+
+	for (opcode, operands) in instructions.iter().cloned() {
+		words.push((operands.len() as u32 + 1) << 16 | opcode);
+		words.extend_from_slice(operands);
+	}
+
+	words.iter().flat_map(|word| {
+		(0..4).map(move |i| (word >> (i * 8)) as u8)
+	}).collect()
+}
+
+#[derive(Copy, Clone)] #[repr(C)] struct Paint {
+	color: [f32; 4],
+	offsets: [[f32; 4]; 2],
+}
+
+#[test]
+fn custom_shapes() {
+	let mut display = display(false);
+	// Two inputs, and a uniform block ending in
+	// `struct { vec4 color; vec4 offsets[2]; }` at offset 80.
+	let vert = spirv(&[
+		(22, &[1, 32]), // float
+		(23, &[2, 1, 4]), // vec4
+		(24, &[3, 2, 4]), // mat4
+		(21, &[4, 32, 0]), // uint
+		(43, &[4, 5, 2]), // 2u
+		(28, &[6, 2, 5]), // vec4[2]
+		(30, &[7, 2, 6]), // Paint
+		(30, &[8, 3, 4, 7]), // UniformBuffer
+		(32, &[9, 2, 8]),
+		(59, &[9, 10, 2]),
+		(32, &[11, 1, 2]),
+		(59, &[11, 12, 1]),
+		(59, &[11, 13, 1]),
+		(71, &[6, 6, 16]), // ArrayStride
+		(71, &[10, 33, 0]), // Binding
+		(71, &[12, 30, 0]), // Location
+		(71, &[13, 30, 1]),
+		(72, &[7, 0, 35, 0]), // Offset
+		(72, &[7, 1, 35, 16]),
+		(72, &[8, 0, 35, 0]),
+		(72, &[8, 0, 7, 16]), // MatrixStride
+		(72, &[8, 1, 35, 64]),
+		(72, &[8, 2, 35, 80]),
+	]);
+	// One texture.
+	let frag = spirv(&[
+		(27, &[21, 20]),
+		(32, &[22, 0, 21]),
+		(59, &[22, 23, 0]),
+	]);
+
+	assert!(display.style::<[f32; 4]>(&vert, &frag, 1, 2).is_err());
+	let style = display.style::<Paint>(&vert, &frag, 1, 2).unwrap();
+	let other = display.style::<Paint>(&vert, &frag, 1, 2).unwrap();
+
+	let model = display.model(&QUAD, FANS.to_vec());
+	let texture = display.texture((1, 1), &VFrame(vec![255; 4]));
+	let tc = display.texcoords(&QUAD);
+	let colors = display.gradient(&[1.0; 16]);
+	let paint = Paint { color: [1.0; 4], offsets: [[0.5; 4]; 2] };
+	let bytes = |paint: &Paint| {
+		let floats = paint.color.iter()
+			.chain(paint.offsets.iter().flat_map(|o| o.iter()));
+
+		floats.flat_map(|f| (0..4).map(move |i| {
+			(f.to_bits() >> (i * 8)) as u8
+		})).collect::<Vec<u8>>()
+	};
+
+	// The uniform follows the renderer's part.
+	let shape = display.shape_custom(&style, &model, Transform::IDENTITY,
+		paint, Some(&texture), Some(&tc), None, false, false, false)
+		.unwrap();
+	assert_eq!(display.check_shape(&shape), Ok(()));
+	let sprite = last_sprite(display.device().calls());
+	assert!(display.device().calls().iter().any(|call| match *call {
+		Call::Sprite { sprite: s, ref uniform, texture: Some(_), .. }
+			=> s == sprite && uniform[80..] == bytes(&paint)[..],
+		_ => false,
+	}));
+
+	// Shapes without the style's texture, or with too many vertex buffers,
+	// aren't made.
+	let count = display.device().calls().len();
+	assert_eq!(display.shape_custom(&style, &model, Transform::IDENTITY,
+		paint, None, Some(&tc), None, false, false, false).err(),
+		Some(Error::MismatchedTextureCount));
+	assert_eq!(display.shape_custom(&style, &model, Transform::IDENTITY,
+		paint, Some(&texture), Some(&tc), Some(&colors), false, false,
+		false).err(), Some(Error::MismatchedVertexCount));
+	assert_eq!(display.device().calls().len(), count);
+
+	// Changing the uniform writes the sprite before it's drawn, but only
+	// with its own style.
+	let shape = display.shape_custom(&style, &model, Transform::IDENTITY,
+		paint, Some(&texture), Some(&tc), None, false, false, false)
+		.unwrap();
+	let sprite = last_sprites(display.device().calls())[0];
+	let paint = Paint { color: [0.0; 4], .. paint };
+	assert_eq!(display.set_uniform(&shape, &other, paint),
+		Err(Error::InvalidHandle(HandleError::Foreign)));
+	display.set_uniform(&shape, &style, paint).unwrap();
+	display.update();
+	assert!(display.device().calls().iter().any(|call| match *call {
+		Call::SpriteWrite { sprite: s, ref uniform } => {
+			s == sprite && uniform.get(80..) == Some(&bytes(&paint))
+		}
+		_ => false,
+	}));
+}
+
+#[test]
+fn pipeline_cache() {
+	let dir = std::env::temp_dir().join(format!("adi_gpu_vulkan-cache-{}",
+		std::process::id()));
+	let file = dir.join("pipeline_cache");
+	let config = DisplayConfig {
+		pipeline_cache: Some(dir.clone()),
+		.. DisplayConfig::default()
+	};
+	let seed = |data: &[u8]| {
+		std::fs::create_dir_all(&dir).unwrap();
+		std::fs::write(&file, data).unwrap();
+
+		let display = adi_gpu_vulkan::with_device(Recorder::new(),
+			(64, 64), config.clone()).unwrap();
+
+		let calls = display.device().calls();
+
+		calls.iter().filter_map(|call| match *call {
+			Call::PipelineCache { ref data, .. } => {
+				Some(data.clone())
+			}
+			_ => None,
+		}).next().unwrap()
+	};
+
+	// A cache from this device is used, and saved again on drop.
+	let mut cache = Recorder::pipeline_cache_header();
+	cache.extend_from_slice(b"pipelines");
+	assert_eq!(seed(&cache), cache);
+	assert_eq!(std::fs::read(&file).unwrap(), cache);
+	assert!(!dir.join("pipeline_cache.tmp").exists());
+
+	// A cache from another driver, or garbage, is thrown away.
+	let mut stale = cache.clone();
+	stale[16] ^= 1;
+	assert_eq!(seed(&stale), vec![]);
+	assert_eq!(seed(b"garbage"), vec![]);
+	assert_eq!(std::fs::read(&file).unwrap(),
+		Recorder::pipeline_cache_header());
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn resize_restyles_shapes() {
+	let mut display = display(false);
+	let model = display.model(&QUAD, FANS.to_vec());
+	display.shape_solid(&model, Transform::IDENTITY, [1.0, 1.0, 1.0, 1.0],
+		false, false, true);
+	let sprite = last_sprite(display.device().calls());
+
+	// Minimizing doesn't touch the swapchain, or draw.
+	let count = display.device().calls().len();
+	display.resize((0, 0));
+	display.update();
+	assert_eq!(display.device().calls().len(), count);
+
+	display.resize((32, 32));
+	let calls = display.device().calls();
+	let render_pass = calls.iter().rev().filter_map(|call| match *call {
+		Call::SwapchainNew { render_pass, .. } => Some(render_pass),
+		_ => None,
+	}).next().unwrap();
+	let style = calls.iter().filter_map(|call| match *call {
+		Call::SpriteRestyle { sprite: s, style } if s == sprite => {
+			Some(style)
+		}
+		_ => None,
+	}).next().unwrap();
+
+	// The shape's new style is made for the new render pass.
+	assert!(calls.iter().any(|call| match *call {
+		Call::Style { style: s, render_pass: r, .. } => {
+			s == style && r == render_pass
+		}
+		_ => false,
+	}));
+}
+
+#[test]
+fn errors_make_invalid_shapes() {
+	let mut display = display(false);
+	let model = display.model(&QUAD, FANS.to_vec());
+	let texture = display.texture((1, 1), &VFrame(vec![255; 4]));
+
+	// Two texcoords for four vertices.
+	let tc = display.texcoords(&QUAD[..8]);
+	let shape = display.shape_texture(&model, Transform::IDENTITY,
+		&texture, tc, false, false, false);
+	assert!(display.check_shape(&shape).is_err());
+	assert_eq!(display.last_error(), Some(Error::MismatchedVertexCount));
+	assert_eq!(display.last_error(), None);
+
+	// The null shape is still a GUI shape, so it's ignored like one.
+	assert_eq!(display.gui_layer(&shape, 1), Err(HandleError::Foreign));
+	display.drop_shape(&shape);
+	assert_eq!(display.last_error(),
+		Some(Error::InvalidHandle(HandleError::Foreign)));
+
+	// A model that doesn't fit in memory.
+	display.device_mut().fail(Error::OutOfDeviceMemory);
+	let model = display.model(&QUAD, FANS.to_vec());
+	assert_eq!(display.last_error(), Some(Error::OutOfDeviceMemory));
+	let shape = display.shape_solid(&model, Transform::IDENTITY,
+		[1.0, 1.0, 1.0, 1.0], false, false, false);
+	assert!(display.check_shape(&shape).is_err());
+	assert_eq!(display.last_error(),
+		Some(Error::InvalidHandle(HandleError::Foreign)));
+}
+
+#[test]
+fn update_errors_are_kept() {
+	let mut display = display(false);
+	display.update();
+
+	// The frame is skipped instead of panicking.
+	display.device_mut().fail(Error::OutOfDeviceMemory);
+	assert!(display.update().is_none());
+	assert_eq!(display.last_error(), Some(Error::OutOfDeviceMemory));
+
+	display.update();
+	assert_eq!(display.last_error(), None);
+}
+
+#[test]
+fn surface_lost_remakes_swapchain() {
+	let mut display = display(false);
+	let swapchains = |calls: &[Call]| calls.iter().filter(|call| {
+		match **call {
+			Call::SwapchainNew { .. } => true,
+			_ => false,
+		}
+	}).count();
+	let before = swapchains(display.device().calls());
+
+	display.device_mut().fail(Error::SurfaceLost);
+	display.update();
+	assert_eq!(swapchains(display.device().calls()), before + 1);
+}
+
+#[test]
+fn suboptimal_presents_then_remakes_swapchain() {
+	let mut display = display(false);
+	display.update();
+	display.device_mut().suboptimal();
+	display.device_mut().clear();
+	display.update();
+
+	// The image is drawn and presented before the swapchain is remade.
+	let calls = display.device().calls();
+	let present = calls.iter().position(|call| match *call {
+		Call::QueuePresent(_) => true,
+		_ => false,
+	}).unwrap();
+	let swapchain = calls.iter().position(|call| match *call {
+		Call::SwapchainNew { .. } => true,
+		_ => false,
+	}).unwrap();
+	assert!(present < swapchain);
+}
+
+#[test]
+fn device_lost_uploads_again() {
+	let mut display = display(false);
+	let model = display.model(&QUAD, FANS.to_vec());
+	let texture = display.texture((1, 1), &VFrame(vec![1, 2, 3, 4]));
+	let tc = display.texcoords(&QUAD);
+	let shape = display.shape_texture(&model, Transform::IDENTITY,
+		&texture, tc, false, false, false);
+	display.transform(&shape, Transform::IDENTITY.t(vec3!(1.0, 0.0, 0.0)));
+	let old_sprites = last_sprites(display.device().calls());
+	display.update();
+
+	display.device_mut().clear();
+	display.device_mut().fail(Error::DeviceLost);
+	display.update();
+	assert!(display.device_lost());
+	assert!(!display.device_lost());
+
+	let calls = display.device().calls();
+	let reset = calls.iter().position(|call| *call == Call::DeviceReset)
+		.unwrap();
+	let (before, after) = calls.split_at(reset);
+
+	// Everything on the lost device is freed before it's reset.
+	for &old_sprite in old_sprites.iter() {
+		assert!(before.contains(&Call::SpriteDrop(old_sprite)));
+	}
+	assert_eq!(before.iter().filter(|call| match **call {
+		Call::BufferDrop(_) => true,
+		_ => false,
+	}).count(), 2);
+	assert!(before.iter().any(|call| match *call {
+		Call::ImageDrop(_) => true,
+		_ => false,
+	}));
+	assert!(!after.iter().any(|call| match *call {
+		Call::SpriteDrop(_) | Call::BufferDrop(_) | Call::ImageDrop(_)
+			=> true,
+		_ => false,
+	}));
+
+	// The model, texcoords and texture are uploaded again.
+	assert_eq!(after.iter().filter(|call| match **call {
+		Call::Buffer { ref data, .. } => data[..] == QUAD[..],
+		_ => false,
+	}).count(), 2);
+	assert!(after.iter().any(|call| match *call {
+		Call::ImageWrite { width: 1, height: 1, .. } => true,
+		_ => false,
+	}));
+
+	// And the shape is made again, and moved where it was before it's
+	// drawn.
+	let sprites = last_sprites(after);
+	assert_eq!(sprites.len(), old_sprites.len());
+	for &old_sprite in old_sprites.iter() {
+		assert!(!sprites.contains(&old_sprite));
+	}
+	assert_eq!(display.check_shape(&shape), Ok(()));
+
+	display.device_mut().clear();
+	display.update();
+	assert!(written(display.device().calls(), sprites[0]));
+	assert_eq!(drawn(display.device().calls()), vec![sprites[0]]);
+}
+
+#[test]
+fn debug_names_objects() {
+	let names = |debug| {
+		let config = DisplayConfig {
+			debug, .. DisplayConfig::default()
+		};
+		let mut display = adi_gpu_vulkan::with_device(Recorder::new(),
+			(64, 64), config).unwrap();
+		display.model(&QUAD, FANS.to_vec());
+		display.texture((1, 1), &VFrame(vec![255; 4]));
+
+		display.device().calls().iter().filter_map(|call| match *call {
+			Call::Name { ref name, .. } => Some(name.clone()),
+			_ => None,
+		}).collect::<Vec<_>>()
+	};
+
+	assert_eq!(names(DebugMode::Off), Vec::<String>::new());
+
+	let names = names(DebugMode::Log);
+	assert!(names.contains(&"solid style (blended)".to_string()));
+	assert!(names.iter().any(|name| name.starts_with("Model 0x")));
+	assert!(names.iter().any(|name| name.starts_with("Texture 0x")));
+}
+
+#[test]
+fn samples_follow_device_limits() {
+	// Only 1 and 4 samples per pixel are supported.
+	let mut display = adi_gpu_vulkan::with_device(
+		Recorder::new().sample_counts(0b0101), (64, 64),
+		DisplayConfig::default()).unwrap();
+	let samples = |calls: &[Call]| calls.iter().rev().filter_map(|call| {
+		match *call {
+			Call::SwapchainNew { samples, .. } => Some(samples),
+			_ => None,
+		}
+	}).next().unwrap();
+	assert_eq!(display.samples(), 4);
+	assert_eq!(samples(display.device().calls()), VkSampleCount::Sc4);
+
+	// 2 isn't supported, so it's lowered to 1, and the styles are made
+	// again to match.
+	display.set_samples(2).unwrap();
+	assert_eq!(display.samples(), 1);
+	let calls = display.device().calls();
+	let new = calls.iter().rposition(|call| match *call {
+		Call::SwapchainNew { .. } => true,
+		_ => false,
+	}).unwrap();
+	assert_eq!(samples(calls), VkSampleCount::Sc1);
+	assert!(calls[new..].iter().any(|call| match *call {
+		Call::Style { samples, .. } => samples == VkSampleCount::Sc1,
+		_ => false,
+	}));
+
+	// Asking for what's already selected doesn't remake anything.
+	let count = display.device().calls().len();
+	display.set_samples(1).unwrap();
+	assert_eq!(display.device().calls().len(), count);
+}
+
+#[test]
+fn mipmaps() {
+	let config = TextureConfig {
+		mipmaps: true, .. TextureConfig::default()
+	};
+	// A 2x2 texture has a 1x1 level, the average of its pixels.
+	let rgba = VFrame(vec![0, 0, 0, 0, 40, 40, 40, 40, 80, 80, 80, 80, 120,
+		120, 120, 120]);
+	let images = |calls: &[Call]| calls.iter().filter_map(|call| {
+		match *call {
+			Call::Image { image, levels, .. } => {
+				Some((image, levels))
+			}
+			_ => None,
+		}
+	}).collect::<Vec<_>>();
+
+	// Blitted on the GPU, into an optimal tiled image.
+	let mut display = display(false);
+	display.texture_with_config((2, 2), &rgba, config).unwrap();
+	let calls = display.device().calls();
+	let (image, levels) = *images(calls).last().unwrap();
+	assert_eq!(levels, 2);
+	assert!(calls.contains(&Call::Mipmaps { image, levels: 2 }));
+
+	// Or averaged on the CPU, if the format can't be blitted.
+	let mut display = adi_gpu_vulkan::with_device(
+		Recorder::new().no_linear_blit(), (64, 64),
+		DisplayConfig::default()).unwrap();
+	display.texture_with_config((2, 2), &rgba, config).unwrap();
+	let calls = display.device().calls();
+	let made = images(calls);
+	let (mappable, _) = made[made.len() - 3];
+	let (image, _) = made[made.len() - 2];
+	let (staging, _) = made[made.len() - 1];
+	assert!(calls.contains(&Call::ImageCopy {
+		src: staging, dst: image, level: 1
+	}));
+	assert_eq!(calls.last(), Some(&Call::ImageDrop(staging)));
+	// The mappable image keeps the first level, for region uploads.
+	assert_eq!(display.device().pixels(mappable), Some(&rgba.0[..]));
+	// sRGB colors are averaged as linear values, but not alpha.
+	assert_eq!(display.device().pixels(image), Some(&rgba.0[..]));
+	assert_eq!(display.device().level_pixels(image, 1),
+		Some(&[76, 76, 76, 60][..]));
+
+	// Linear textures are averaged as stored.
+	display.texture_with_config((2, 2), &rgba, TextureConfig {
+		color_space: ColorSpace::Linear, .. config
+	}).unwrap();
+	let made = images(display.device().calls());
+	let (image, _) = made[made.len() - 2];
+	assert_eq!(display.device().level_pixels(image, 1),
+		Some(&[60; 4][..]));
+}
+
+#[test]
+fn samplers_are_shared() {
+	let mut display = adi_gpu_vulkan::with_device(
+		Recorder::new().anisotropy(4), (64, 64),
+		DisplayConfig::default()).unwrap();
+	let model = display.model(&QUAD, FANS.to_vec());
+	let pixel = VFrame(vec![255; 4]);
+	let nearest = TextureConfig {
+		sampler: SamplerDesc {
+			min_filter: Filter::Nearest,
+			mag_filter: Filter::Nearest,
+			anisotropy: 16,
+			.. SamplerDesc::default()
+		},
+		.. TextureConfig::default()
+	};
+	let textures = [
+		display.texture((1, 1), &pixel),
+		display.texture((1, 1), &pixel),
+		display.texture_with_config((1, 1), &pixel, nearest).unwrap(),
+	];
+	for texture in textures.iter() {
+		let tc = display.texcoords(&QUAD);
+		display.shape_texture(&model, Transform::IDENTITY, texture, tc,
+			false, false, false);
+	}
+
+	let calls = display.device().calls();
+	let samplers = calls.iter().filter_map(|call| match *call {
+		Call::Sampler { sampler, desc } => Some((sampler, desc)),
+		_ => None,
+	}).collect::<Vec<_>>();
+	let used = calls.iter().filter_map(|call| match *call {
+		Call::Sprite { sampler, .. } => sampler,
+		_ => None,
+	}).collect::<Vec<_>>();
+
+	// The first two textures share a sampler, and anisotropy is lowered
+	// to what's supported.  Each shape has a sprite for both frames in
+	// flight.
+	assert_eq!(samplers.len(), 2);
+	assert_eq!(samplers[1].1.anisotropy, 4);
+	assert_eq!(used, vec![samplers[0].0, samplers[0].0, samplers[0].0,
+		samplers[0].0, samplers[1].0, samplers[1].0]);
+}
+
+#[test]
+fn staged_matches_linear() {
+	let rgba = VFrame(vec![255, 128, 0, 255, 0, 64, 192, 128]);
+	// Get the format and pixels of the image a textured shape samples.
+	let sampled = |staged, color_space| {
+		let mut display = display(staged);
+		let model = display.model(&QUAD, FANS.to_vec());
+		let tc = display.texcoords(&QUAD);
+		let config = TextureConfig {
+			color_space, .. TextureConfig::default()
+		};
+		let texture = display.texture_with_config((2, 1), &rgba,
+			config).unwrap();
+		display.shape_texture(&model, Transform::IDENTITY, &texture,
+			tc, false, false, false);
+
+		let calls = display.device().calls();
+		let image = calls.iter().rev().filter_map(|call| match *call {
+			Call::Sprite { texture, .. } => texture,
+			_ => None,
+		}).next().unwrap();
+		let format = calls.iter().filter_map(|call| match *call {
+			Call::Image { image: i, format, .. } if i == image => {
+				Some(format)
+			}
+			_ => None,
+		}).next().unwrap();
+
+		(format, display.device().pixels(image).unwrap().to_vec())
+	};
+
+	for &color_space in [ColorSpace::Srgb, ColorSpace::Linear].iter() {
+		let linear = sampled(false, color_space);
+		let staged = sampled(true, color_space);
+
+		assert_eq!(linear, staged);
+		assert_eq!(linear.1, rgba.0);
+	}
+	assert_ne!(sampled(false, ColorSpace::Srgb).0,
+		sampled(false, ColorSpace::Linear).0);
+}
+
+#[test]
+fn texture_regions() {
+	for &staged in [false, true].iter() {
+		let mut display = display(staged);
+		let texture = display.texture((2, 2), &VFrame(vec![0; 16]));
+		let (image, _) = last_image(display.device().calls());
+		let count = display.device().calls().len();
+
+		// The right column.
+		display.set_texture_region(&texture, 1, 0, 1, 2,
+			&VFrame(vec![9; 8])).unwrap();
+		let calls = &display.device().calls()[count..];
+		assert!(calls.iter().all(|call| match *call {
+			Call::ImageWrite { .. } => false,
+			Call::ImageCopy { .. } => false,
+			_ => true,
+		}));
+		assert_eq!(calls.iter().any(|call| match *call {
+			Call::ImageCopyRegion { dst, x: 1, y: 0, width: 1,
+				height: 2, .. } => dst == image,
+			_ => false,
+		}), staged);
+		assert_eq!(display.device().pixels(image).unwrap(), &[0, 0, 0,
+			0, 9, 9, 9, 9, 0, 0, 0, 0, 9, 9, 9, 9][..]);
+
+		// Regions must fit in the texture, and be filled.
+		assert_eq!(display.set_texture_region(&texture, 1, 1, 2, 1,
+			&VFrame(vec![9; 8])), Err(Error::InvalidRegion));
+		assert_eq!(display.set_texture_region(&texture, 0, 0, 1, 1,
+			&VFrame(vec![9; 8])), Err(Error::InvalidRegion));
+
+		// Empty regions don't touch the texture.
+		let count = display.device().calls().len();
+		assert_eq!(display.set_texture_region(&texture, 2, 0, 0, 2,
+			&VFrame(vec![])), Ok(()));
+		assert_eq!(display.set_texture_region(&texture, 0, 1, 2, 0,
+			&VFrame(vec![])), Ok(()));
+		assert_eq!(display.device().calls().len(), count);
+	}
+
+	// Mip levels made on the CPU are made again from the whole first
+	// level, which the mappable image still has.
+	let mut display = adi_gpu_vulkan::with_device(
+		Recorder::new().no_linear_blit(), (64, 64),
+		DisplayConfig::default()).unwrap();
+	let texture = display.texture_with_config((2, 2), &VFrame(vec![0, 0, 0,
+		0, 80, 80, 80, 80, 80, 80, 80, 80, 0, 0, 0, 0]), TextureConfig {
+			mipmaps: true,
+			color_space: ColorSpace::Linear,
+			.. TextureConfig::default()
+		}).unwrap();
+	let made = display.device().calls().iter().filter_map(|call| {
+		match *call {
+			Call::Image { image, levels, .. } => {
+				Some((image, levels))
+			}
+			_ => None,
+		}
+	}).collect::<Vec<_>>();
+	let at = made.iter().rposition(|&(_, levels)| levels == 2).unwrap();
+	let (mappable, image) = (made[at - 1].0, made[at].0);
+	display.set_texture_region(&texture, 1, 1, 1, 1, &VFrame(vec![40; 4]))
+		.unwrap();
+	let level = [0, 0, 0, 0, 80, 80, 80, 80, 80, 80, 80, 80, 40, 40, 40,
+		40];
+	assert_eq!(display.device().pixels(mappable).unwrap(), &level[..]);
+	assert_eq!(display.device().level_pixels(image, 1).unwrap(),
+		&[50; 4][..]);
+}
+
+#[test]
+fn texture_formats() {
+	let r8 = TextureConfig {
+		format: TextureFormat::R8,
+		.. TextureConfig::default()
+	};
+
+	for &staged in [false, true].iter() {
+		let mut display = display(staged);
+		let texture = display.texture_with_config((2, 2),
+			&VFrame(vec![1, 2, 3, 4]), r8).unwrap();
+		let (image, format) = last_image(display.device().calls());
+		assert_eq!(format, VkFormat::R8Unorm);
+		assert_eq!(display.device().pixels(image).unwrap(),
+			&[1, 2, 3, 4][..]);
+
+		// Regions are one byte per pixel too.
+		display.set_texture_region(&texture, 1, 0, 1, 2,
+			&VFrame(vec![9, 9])).unwrap();
+		assert_eq!(display.device().pixels(image).unwrap(),
+			&[1, 9, 3, 9][..]);
+		assert_eq!(display.set_texture_region(&texture, 0, 0, 1, 1,
+			&VFrame(vec![9; 4])), Err(Error::InvalidRegion));
+	}
+
+	// Depth can only be sampled from optimal tiled images, and can't be
+	// copied from linear tiled ones, so it's written through a buffer.
+	let mut display = display(false);
+	display.device_mut().clear();
+	let depth = [0.5f32.to_bits(), 1.0f32.to_bits()].iter()
+		.flat_map(|bits| (0..4).map(move |i| (bits >> (i * 8)) as u8))
+		.collect::<Vec<u8>>();
+	let texture = display.texture_with_config((2, 1),
+		&VFrame(depth.clone()), TextureConfig {
+			format: TextureFormat::Depth32F,
+			.. TextureConfig::default()
+		}).unwrap();
+	let calls = display.device().calls();
+	let (image, format) = last_image(calls);
+	assert_eq!(format, VkFormat::D32Sfloat);
+	assert_eq!(calls.iter().filter(|call| match **call {
+		Call::Image { .. } => true,
+		_ => false,
+	}).count(), 1);
+	assert!(calls.contains(&Call::ImageWriteDepth {
+		image, x: 0, y: 0, width: 2, height: 1
+	}));
+	assert_eq!(display.device().pixels(image), Some(&depth[..]));
+
+	// Regions too.
+	display.set_texture_region(&texture, 1, 0, 1, 1, &VFrame(depth[..4]
+		.to_vec())).unwrap();
+	assert!(display.device().calls().contains(&Call::ImageWriteDepth {
+		image, x: 1, y: 0, width: 1, height: 1
+	}));
+	assert_eq!(display.device().pixels(image).unwrap()[4..], depth[..4]);
+
+	// Formats the GPU can't use make no image.
+	let mut display = adi_gpu_vulkan::with_device(
+		Recorder::new().unsupported(VkFormat::R8Unorm), (64, 64),
+		DisplayConfig::default()).unwrap();
+	display.device_mut().clear();
+	assert_eq!(display.texture_with_config((2, 2), &VFrame(vec![0; 4]),
+		r8).err(), Some(Error::UnsupportedFormat));
+	assert!(display.device().calls().iter().all(|call| match *call {
+		Call::Image { .. } => false,
+		_ => true,
+	}));
+
+	// Float formats can't have mip levels made on the CPU.
+	let mut display = adi_gpu_vulkan::with_device(
+		Recorder::new().no_linear_blit(), (64, 64),
+		DisplayConfig::default()).unwrap();
+	display.device_mut().clear();
+	assert_eq!(display.texture_with_config((2, 2), &VFrame(vec![0; 32]),
+		TextureConfig {
+			format: TextureFormat::Rgba16F,
+			mipmaps: true,
+			.. TextureConfig::default()
+		}).err(), Some(Error::UnsupportedFormat));
+	assert!(display.device().calls().iter().all(|call| match *call {
+		Call::Image { .. } => false,
+		_ => true,
+	}));
+}